@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS batch_jobs
+(
+    id              SERIAL PRIMARY KEY,
+    idempotency_key TEXT   NOT NULL UNIQUE,
+    kind            TEXT   NOT NULL,
+    created_at      BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS batch_job_items
+(
+    id          SERIAL PRIMARY KEY,
+    job_id      INT4 NOT NULL REFERENCES batch_jobs (id),
+    item_index  INT4 NOT NULL,
+    description TEXT,
+    asset_id    TEXT,
+    status      TEXT NOT NULL,
+    txn_hash    TEXT,
+    error       TEXT,
+    UNIQUE (job_id, item_index)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE batch_job_items
+    DROP COLUMN IF EXISTS new_owner;
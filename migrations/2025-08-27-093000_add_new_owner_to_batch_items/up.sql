@@ -0,0 +1,2 @@
+ALTER TABLE batch_job_items
+    ADD COLUMN IF NOT EXISTS new_owner TEXT;
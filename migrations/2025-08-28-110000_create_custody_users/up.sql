@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS custody_users
+(
+    id            SERIAL PRIMARY KEY,
+    username      TEXT   NOT NULL UNIQUE,
+    address       TEXT   NOT NULL UNIQUE,
+    encrypted_key TEXT   NOT NULL,
+    created_at    BIGINT NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS api_client_quotas;
+DROP TABLE IF EXISTS gas_spend;
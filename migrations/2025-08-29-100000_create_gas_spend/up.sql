@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS gas_spend
+(
+    id       SERIAL PRIMARY KEY,
+    txn_hash TEXT    NOT NULL UNIQUE,
+    wallet   TEXT    NOT NULL,
+    client   TEXT    NOT NULL,
+    cost_wei BIGINT  NOT NULL,
+    settled  BOOLEAN NOT NULL DEFAULT FALSE,
+    spent_at BIGINT  NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_gas_spend_wallet_spent_at ON gas_spend (wallet, spent_at);
+CREATE INDEX IF NOT EXISTS idx_gas_spend_client_spent_at ON gas_spend (client, spent_at);
+
+CREATE TABLE IF NOT EXISTS api_client_quotas
+(
+    client      TEXT PRIMARY KEY,
+    max_day_wei BIGINT NOT NULL
+);
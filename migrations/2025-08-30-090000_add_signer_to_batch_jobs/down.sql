@@ -0,0 +1,2 @@
+ALTER TABLE batch_jobs
+    DROP COLUMN IF EXISTS signer;
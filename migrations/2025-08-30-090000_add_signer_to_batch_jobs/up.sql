@@ -0,0 +1,2 @@
+ALTER TABLE batch_jobs
+    ADD COLUMN IF NOT EXISTS signer TEXT;
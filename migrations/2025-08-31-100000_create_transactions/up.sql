@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS transactions
+(
+    txn_hash                TEXT PRIMARY KEY,
+    from_address            TEXT    NOT NULL,
+    to_address              TEXT,
+    nonce                   BIGINT,
+    block_number            BIGINT,
+    status                  TEXT    NOT NULL,
+    gas_used                BIGINT,
+    effective_gas_price_wei BIGINT,
+    l1_fee_wei              BIGINT,
+    total_fee_wei           BIGINT,
+    sent_by_api             BOOLEAN NOT NULL DEFAULT FALSE,
+    recorded_at             BIGINT  NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_from_address ON transactions (from_address);
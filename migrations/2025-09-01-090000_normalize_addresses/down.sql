@@ -0,0 +1,3 @@
+-- The original checksum casing is not kept, and lowercase addresses are equally valid,
+-- so there is nothing to undo
+SELECT 1;
@@ -0,0 +1,10 @@
+-- Addresses are stored as lowercase 0x-prefixed hex. The indexer used to write EIP-55
+-- checksummed owners, so lookups by owner missed rows depending on which path wrote them.
+UPDATE assets SET owner = lower(owner) WHERE owner <> lower(owner);
+UPDATE transfers SET old_owner = lower(old_owner) WHERE old_owner <> lower(old_owner);
+UPDATE transfers SET new_owner = lower(new_owner) WHERE new_owner <> lower(new_owner);
+UPDATE transactions SET from_address = lower(from_address) WHERE from_address <> lower(from_address);
+UPDATE transactions SET to_address = lower(to_address) WHERE to_address <> lower(to_address);
+UPDATE gas_spend SET wallet = lower(wallet) WHERE wallet <> lower(wallet);
+UPDATE batch_jobs SET signer = lower(signer) WHERE signer <> lower(signer);
+UPDATE custody_users SET address = lower(address) WHERE address <> lower(address);
@@ -0,0 +1,44 @@
+-- One row per AssetRegistered or OwnershipTransferred event, so registrations and transfers
+-- can be searched as a single feed. from_address is NULL for registrations.
+CREATE TABLE IF NOT EXISTS asset_events
+(
+    id           SERIAL PRIMARY KEY,
+    kind         TEXT   NOT NULL,
+    asset_id     TEXT   NOT NULL,
+    from_address TEXT,
+    to_address   TEXT   NOT NULL,
+    timestamp    BIGINT NOT NULL,
+    block_number BIGINT,
+    txn_hash     TEXT
+);
+
+-- An asset is registered once and moved at most once per transaction, so the indexer and the
+-- API can both record the same event without duplicating it
+CREATE UNIQUE INDEX IF NOT EXISTS uq_asset_events_registered ON asset_events (asset_id) WHERE kind = 'registered';
+CREATE UNIQUE INDEX IF NOT EXISTS uq_asset_events_transferred ON asset_events (asset_id, txn_hash) WHERE kind = 'transferred';
+
+CREATE INDEX IF NOT EXISTS idx_asset_events_timestamp ON asset_events (timestamp, id);
+CREATE INDEX IF NOT EXISTS idx_asset_events_asset_id ON asset_events (asset_id, timestamp, id);
+CREATE INDEX IF NOT EXISTS idx_asset_events_from_address ON asset_events (from_address, timestamp, id);
+CREATE INDEX IF NOT EXISTS idx_asset_events_to_address ON asset_events (to_address, timestamp, id);
+CREATE INDEX IF NOT EXISTS idx_asset_events_block_number ON asset_events (block_number, id);
+CREATE INDEX IF NOT EXISTS idx_asset_events_txn_hash ON asset_events (txn_hash);
+
+-- Backfill from what is already indexed. The registrant is the old owner of an asset's first
+-- transfer, or its current owner if it never moved; the registration transaction is unknown.
+INSERT INTO asset_events (kind, asset_id, from_address, to_address, timestamp)
+SELECT 'registered',
+       a.asset_id,
+       NULL,
+       COALESCE((SELECT t.old_owner FROM transfers t WHERE t.asset_id = a.asset_id ORDER BY t.id LIMIT 1),
+                a.owner),
+       a.registered_at
+FROM assets a
+ON CONFLICT DO NOTHING;
+
+INSERT INTO asset_events (kind, asset_id, from_address, to_address, timestamp, block_number, txn_hash)
+SELECT 'transferred', t.asset_id, t.old_owner, t.new_owner, t.timestamp, tx.block_number, t.txn_hash
+FROM transfers t
+         LEFT JOIN transactions tx ON tx.txn_hash = t.txn_hash
+ORDER BY t.id
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_assets_description_tsv;
+ALTER TABLE assets DROP COLUMN IF EXISTS description_tsv;
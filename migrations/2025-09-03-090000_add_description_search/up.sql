@@ -0,0 +1,7 @@
+-- Full-text search over asset descriptions. The column is generated, so Postgres rewrites it
+-- on every insert or update of description, whether the indexer or the API made it.
+ALTER TABLE assets
+    ADD COLUMN IF NOT EXISTS description_tsv tsvector
+        GENERATED ALWAYS AS (to_tsvector('english', description)) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_assets_description_tsv ON assets USING GIN (description_tsv);
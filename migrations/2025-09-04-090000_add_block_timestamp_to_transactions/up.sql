@@ -0,0 +1,3 @@
+-- Time of the block a transaction was mined in, so event times are chain times rather than the
+-- moment the indexer happened to see them. Unknown for transactions recorded before this.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS block_timestamp BIGINT;
@@ -0,0 +1,34 @@
+-- Endpoints notified of registry events. A NULL filter matches everything; address matches
+-- either side of an event.
+CREATE TABLE IF NOT EXISTS webhook_subscriptions
+(
+    id         SERIAL PRIMARY KEY,
+    url        TEXT    NOT NULL,
+    secret     TEXT    NOT NULL,
+    event_kind TEXT,
+    asset_id   TEXT,
+    address    TEXT,
+    active     BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at BIGINT  NOT NULL
+);
+
+-- Outbox and delivery log: one row per event per matching subscription, written in the same
+-- transaction as the event and worked off by the delivery worker
+CREATE TABLE IF NOT EXISTS webhook_deliveries
+(
+    id              SERIAL PRIMARY KEY,
+    subscription_id INTEGER NOT NULL REFERENCES webhook_subscriptions (id) ON DELETE CASCADE,
+    event_id        INTEGER NOT NULL REFERENCES asset_events (id),
+    status          TEXT    NOT NULL,
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT  NOT NULL,
+    last_attempt_at BIGINT,
+    response_status INTEGER,
+    last_error      TEXT,
+    created_at      BIGINT  NOT NULL,
+    delivered_at    BIGINT,
+    UNIQUE (subscription_id, event_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_subscription ON webhook_deliveries (subscription_id, id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS api_key_writes;
+DROP TABLE IF EXISTS api_keys;
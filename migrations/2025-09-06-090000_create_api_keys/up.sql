@@ -0,0 +1,27 @@
+-- API keys, stored as the SHA-256 of the key. key_prefix is the start of the key, kept so a
+-- key can be recognised in listings without storing it.
+CREATE TABLE IF NOT EXISTS api_keys
+(
+    id           SERIAL PRIMARY KEY,
+    name         TEXT   NOT NULL,
+    key_prefix   TEXT   NOT NULL,
+    key_hash     TEXT   NOT NULL UNIQUE,
+    role         TEXT   NOT NULL,
+    created_at   BIGINT NOT NULL,
+    last_used_at BIGINT,
+    revoked_at   BIGINT
+);
+
+-- One row per request an API key made to a write or admin endpoint
+CREATE TABLE IF NOT EXISTS api_key_writes
+(
+    id         SERIAL PRIMARY KEY,
+    api_key_id INTEGER NOT NULL REFERENCES api_keys (id),
+    method     TEXT    NOT NULL,
+    path       TEXT    NOT NULL,
+    status     INTEGER NOT NULL,
+    request_id TEXT,
+    created_at BIGINT  NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_writes_key ON api_key_writes (api_key_id, id);
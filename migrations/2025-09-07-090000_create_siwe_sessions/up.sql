@@ -0,0 +1,21 @@
+-- Nonces handed out for Sign-In with Ethereum messages. Each signs in at most once.
+CREATE TABLE IF NOT EXISTS siwe_nonces
+(
+    nonce      TEXT PRIMARY KEY,
+    issued_at  BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    used_at    BIGINT
+);
+
+-- Sessions of signed-in wallets, stored as the SHA-256 of the session token
+CREATE TABLE IF NOT EXISTS siwe_sessions
+(
+    id         SERIAL PRIMARY KEY,
+    token_hash TEXT   NOT NULL UNIQUE,
+    address    TEXT   NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_siwe_sessions_address ON siwe_sessions (address);
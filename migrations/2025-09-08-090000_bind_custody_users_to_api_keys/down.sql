@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_custody_users_api_key_id;
+ALTER TABLE custody_users DROP COLUMN IF EXISTS api_key_id;
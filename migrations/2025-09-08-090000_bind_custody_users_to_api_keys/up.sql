@@ -0,0 +1,6 @@
+-- The API key that created a custodial user, and the only one allowed to act as it. Users
+-- created before keys existed have none and cannot be acted as until one is assigned.
+ALTER TABLE custody_users
+    ADD COLUMN IF NOT EXISTS api_key_id INTEGER REFERENCES api_keys (id);
+
+CREATE INDEX IF NOT EXISTS idx_custody_users_api_key_id ON custody_users (api_key_id);
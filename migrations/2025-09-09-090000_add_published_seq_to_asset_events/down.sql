@@ -0,0 +1,2 @@
+ALTER TABLE asset_events DROP COLUMN IF EXISTS published_seq;
+DROP SEQUENCE IF EXISTS asset_events_published_seq;
@@ -0,0 +1,14 @@
+-- The order events went live on /events/stream, assigned when the indexer publishes them. Row
+-- ids can't be used: the API records the events of its own transactions before the indexer
+-- publishes them, so a lower id can go live after a higher one. NULL until published.
+CREATE SEQUENCE IF NOT EXISTS asset_events_published_seq AS BIGINT;
+
+ALTER TABLE asset_events
+    ADD COLUMN IF NOT EXISTS published_seq BIGINT UNIQUE;
+
+-- Everything recorded so far counts as published, in id order
+UPDATE asset_events
+SET published_seq = numbered.seq
+FROM (SELECT id, nextval('asset_events_published_seq') AS seq
+      FROM (SELECT id FROM asset_events ORDER BY id) ordered) numbered
+WHERE asset_events.id = numbered.id;
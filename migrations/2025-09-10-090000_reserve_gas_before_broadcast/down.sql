@@ -0,0 +1,2 @@
+DELETE FROM gas_spend WHERE txn_hash IS NULL;
+ALTER TABLE gas_spend ALTER COLUMN txn_hash SET NOT NULL;
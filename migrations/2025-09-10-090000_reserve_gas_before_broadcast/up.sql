@@ -0,0 +1,3 @@
+-- Spend is now reserved before a transaction is sent, under the same lock as the budget check,
+-- so a reservation exists before its transaction hash does. NULL until the send succeeds.
+ALTER TABLE gas_spend ALTER COLUMN txn_hash DROP NOT NULL;
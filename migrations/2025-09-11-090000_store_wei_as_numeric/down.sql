@@ -0,0 +1,7 @@
+-- Amounts past BIGINT fail the conversion rather than being truncated
+ALTER TABLE transactions
+    ALTER COLUMN effective_gas_price_wei TYPE BIGINT,
+    ALTER COLUMN l1_fee_wei TYPE BIGINT,
+    ALTER COLUMN total_fee_wei TYPE BIGINT;
+ALTER TABLE api_client_quotas ALTER COLUMN max_day_wei TYPE BIGINT;
+ALTER TABLE gas_spend ALTER COLUMN cost_wei TYPE BIGINT;
@@ -0,0 +1,8 @@
+-- Wei amounts can exceed BIGINT (about 9.22 ETH), so store them as whole numbers of up to 78
+-- digits, which holds any uint256
+ALTER TABLE gas_spend ALTER COLUMN cost_wei TYPE NUMERIC(78, 0);
+ALTER TABLE api_client_quotas ALTER COLUMN max_day_wei TYPE NUMERIC(78, 0);
+ALTER TABLE transactions
+    ALTER COLUMN effective_gas_price_wei TYPE NUMERIC(78, 0),
+    ALTER COLUMN l1_fee_wei TYPE NUMERIC(78, 0),
+    ALTER COLUMN total_fee_wei TYPE NUMERIC(78, 0);
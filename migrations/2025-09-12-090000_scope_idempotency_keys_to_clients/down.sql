@@ -0,0 +1,5 @@
+-- Fails if two clients have since used the same key
+ALTER TABLE batch_jobs
+    DROP CONSTRAINT batch_jobs_client_idempotency_key_key,
+    ADD CONSTRAINT batch_jobs_idempotency_key_key UNIQUE (idempotency_key),
+    DROP COLUMN client;
@@ -0,0 +1,11 @@
+-- Clients pick their own idempotency keys, so the same key from two API clients must name two
+-- jobs. Jobs are now unique per client (`api_key:<id>`) and key. Relays are keyed by their
+-- transaction hash, which already identifies them, and stay shared under the client `relay`.
+-- Earlier jobs can't be attributed to a client and are left with an empty one.
+ALTER TABLE batch_jobs
+    ADD COLUMN client TEXT NOT NULL DEFAULT '';
+UPDATE batch_jobs SET client = 'relay' WHERE kind = 'relay';
+ALTER TABLE batch_jobs
+    ALTER COLUMN client DROP DEFAULT,
+    DROP CONSTRAINT batch_jobs_idempotency_key_key,
+    ADD CONSTRAINT batch_jobs_client_idempotency_key_key UNIQUE (client, idempotency_key);
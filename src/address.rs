@@ -0,0 +1,150 @@
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use ethers::types::H160;
+use ethers::utils::to_checksum;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::Write;
+use utoipa::openapi::RefOr;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+use utoipa::{PartialSchema, ToSchema};
+
+// An EVM address in the one form this service stores and compares it in: lowercase,
+// 0x-prefixed hex. Input is accepted in any case, but mixed-case input must carry a valid
+// EIP-55 checksum so a mistyped address is refused rather than silently matching nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct EvmAddress(pub H160);
+
+impl EvmAddress {
+    // `field` names the input in the error message
+    pub fn parse(field: &str, input: &str) -> Result<EvmAddress, String> {
+        let input = input.trim();
+        let digits = input
+            .strip_prefix("0x")
+            .or_else(|| input.strip_prefix("0X"))
+            .unwrap_or(input);
+        if digits.len() != 40 {
+            return Err(format!(
+                "Invalid {} length: expected 20 bytes (40 hex digits), got {} digits",
+                field,
+                digits.len()
+            ));
+        }
+        let bytes =
+            hex::decode(digits).map_err(|e| format!("Invalid {} format: {:?}", field, e))?;
+        let address = H160::from_slice(&bytes);
+
+        let has_lower = digits.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = digits.chars().any(|c| c.is_ascii_uppercase());
+        if has_lower && has_upper && to_checksum(&address, None)[2..] != *digits {
+            return Err(format!("Invalid {} checksum: {}", field, input));
+        }
+        Ok(EvmAddress(address))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl From<H160> for EvmAddress {
+    fn from(address: H160) -> Self {
+        EvmAddress(address)
+    }
+}
+
+impl fmt::Display for EvmAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl Serialize for EvmAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EvmAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = String::deserialize(deserializer)?;
+        EvmAddress::parse("address", &input).map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Text, Pg> for EvmAddress {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.to_string().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for EvmAddress {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let stored = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(EvmAddress::parse("stored address", &stored)?)
+    }
+}
+
+impl PartialSchema for EvmAddress {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some("^(0[xX])?[0-9a-fA-F]{40}$"))
+            .description(Some(
+                "20-byte address as 0x-prefixed hex, returned lowercase. Mixed-case input must be a valid EIP-55 checksum.",
+            ))
+            .examples(["0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"])
+            .into()
+    }
+}
+
+impl ToSchema for EvmAddress {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKSUMMED: &str = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+    const LOWERCASE: &str = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045";
+
+    #[test]
+    fn parse_accepts_any_single_case_and_valid_checksums() {
+        let expected = EvmAddress::parse("owner", LOWERCASE).unwrap();
+        assert_eq!(expected.to_string(), LOWERCASE);
+        assert_eq!(EvmAddress::parse("owner", CHECKSUMMED).unwrap(), expected);
+        assert_eq!(
+            EvmAddress::parse("owner", &LOWERCASE.to_uppercase().replacen("0X", "0x", 1)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            EvmAddress::parse("owner", &format!("  {}\n", &LOWERCASE[2..])).unwrap(),
+            expected
+        );
+        assert_eq!(
+            EvmAddress::parse("owner", &LOWERCASE.replacen("0x", "0X", 1)).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_rejects_a_bad_checksum() {
+        let mistyped = CHECKSUMMED.replacen("dA", "Da", 1);
+        let err = EvmAddress::parse("owner", &mistyped).unwrap_err();
+        assert!(err.starts_with("Invalid owner checksum"), "{}", err);
+    }
+
+    #[test]
+    fn parse_rejects_wrong_length_and_non_hex() {
+        let err = EvmAddress::parse("new_owner", &LOWERCASE[..41]).unwrap_err();
+        assert!(err.starts_with("Invalid new_owner length"), "{}", err);
+        let err = EvmAddress::parse("new_owner", "").unwrap_err();
+        assert!(err.contains("got 0 digits"), "{}", err);
+        let err = EvmAddress::parse("new_owner", &LOWERCASE.replace('a', "g")).unwrap_err();
+        assert!(err.starts_with("Invalid new_owner format"), "{}", err);
+    }
+}
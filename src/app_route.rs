@@ -1,20 +1,44 @@
 use crate::app_state::AppState;
+use crate::auth::{RequireRole, Role, authenticate, authorize};
 use crate::contract_calls::{
-    get_all_contract_assets::get_all_contract_assets, get_asset::get_asset,
-    get_my_assets::get_my_assets, register_asset::register_asset, transfer_asset::transfer_asset,
+    build_transaction::{build_register_transaction, build_transfer_transaction},
+    get_all_contract_assets::get_all_contract_assets,
+    get_asset::get_asset,
+    get_my_assets::get_my_assets,
+    get_owner_assets::get_owner_assets,
+    register_asset::register_asset,
+    register_batch::register_batch,
+    relay::relay_transaction,
+    transfer_asset::transfer_asset,
+    transfer_batch::transfer_batch,
 };
 use crate::handlers::{
     analytics::get_analytics,
-    assets::get_all_assets,
-    search::search_events,
+    api_keys::{create_api_key, list_api_key_writes, list_api_keys, revoke_api_key},
+    asset_audit::{audit_assets, derive_id, verify_asset_id},
+    assets::{get_all_assets, get_asset_detail},
+    custody::{create_custody_user, get_custody_user},
+    graphql::{graphiql, graphql},
+    owners::{get_my_profile, get_owner_profile},
+    search::{search_asset_events, search_events},
+    siwe::{get_siwe_nonce, get_siwe_session, logout_siwe, verify_siwe},
+    stream::stream_events,
+    transactions::get_transaction,
     transfer::{get_assets_by_owner, get_transfers_by_asset, get_transfers_by_date},
+    wallet::{get_metrics, get_wallet_balance},
+    webhooks::{
+        create_webhook, delete_webhook, get_webhook, list_webhook_deliveries, list_webhooks,
+        redeliver_webhook, update_webhook,
+    },
 };
 use std::sync::Arc;
 
+use crate::error::request_id;
 use crate::swagger::ApiDoc;
 use axum::{
     Router,
-    routing::{get, post},
+    middleware::from_fn_with_state,
+    routing::{delete, get, post},
 };
 use ethers::contract::abigen;
 use utoipa::OpenApi;
@@ -28,19 +52,80 @@ abigen!(
 );
 
 pub fn app_router(state: Arc<AppState>) -> Router {
-    let app = Router::new()
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+    let reads = Router::new()
         .route("/assets", get(get_all_assets))
         .route("/transfers/{asset_id}", get(get_transfers_by_asset))
+        .route("/transactions/{hash}", get(get_transaction))
         .route("/assets/owner/{address}", get(get_assets_by_owner))
-        .route("/contract/register", post(register_asset))
+        .route("/assets/derive_id", post(derive_id))
+        .route("/assets/audit", get(audit_assets))
+        .route("/assets/{asset_id}", get(get_asset_detail))
+        .route("/assets/{asset_id}/verify", get(verify_asset_id))
+        .route("/contract/build/register", post(build_register_transaction))
+        .route("/contract/build/transfer", post(build_transfer_transaction))
         .route("/contract/get_asset", post(get_asset))
         .route("/contract/get_all_assets", get(get_all_contract_assets))
         .route("/contract/get_my_assets", get(get_my_assets))
-        .route("/contract/transfer", post(transfer_asset))
+        .route("/contract/assets/owner/{address}", get(get_owner_assets))
+        .route("/owners/me", get(get_my_profile))
+        .route("/owners/{address}", get(get_owner_profile))
+        .route("/custody/users/{username}", get(get_custody_user))
         .route("/search", post(search_events))
+        .route("/search/events", post(search_asset_events))
+        .route("/events/stream", get(stream_events))
+        .route("/graphql", post(graphql))
         .route("/transfers_by_date", get(get_transfers_by_date))
         .route("/analytics", get(get_analytics))
+        .route("/wallet/balance", get(get_wallet_balance))
+        .route("/metrics", get(get_metrics))
+        .route_layer(from_fn_with_state(
+            RequireRole::new(&state, Role::ReadOnly),
+            authorize,
+        ));
+
+    // Everything that sends a transaction or creates a signing key
+    let writes = Router::new()
+        .route("/contract/register", post(register_asset))
+        .route("/contract/register_batch", post(register_batch))
+        .route("/contract/relay", post(relay_transaction))
+        .route("/contract/transfer", post(transfer_asset))
+        .route("/contract/transfer_batch", post(transfer_batch))
+        .route("/custody/users", post(create_custody_user))
+        .route_layer(from_fn_with_state(
+            RequireRole::new(&state, Role::Writer),
+            authorize,
+        ));
+
+    let admin = Router::new()
+        .route("/api_keys", post(create_api_key).get(list_api_keys))
+        .route("/api_keys/{id}", delete(revoke_api_key))
+        .route("/api_keys/{id}/writes", get(list_api_key_writes))
+        .route("/webhooks", post(create_webhook).get(list_webhooks))
+        .route(
+            "/webhooks/{id}",
+            get(get_webhook).put(update_webhook).delete(delete_webhook),
+        )
+        .route("/webhooks/{id}/deliveries", get(list_webhook_deliveries))
+        .route(
+            "/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+            post(redeliver_webhook),
+        )
+        .route_layer(from_fn_with_state(
+            RequireRole::new(&state, Role::Admin),
+            authorize,
+        ));
+
+    let app = Router::new()
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .merge(reads)
+        .merge(writes)
+        .merge(admin)
+        .route("/graphiql", get(graphiql))
+        // Signing in is how a wallet gets access, so it can't sit behind a key
+        .route("/auth/nonce", get(get_siwe_nonce))
+        .route("/auth/verify", post(verify_siwe))
+        .route("/auth/session", get(get_siwe_session))
+        .route("/auth/logout", post(logout_siwe))
         .route(
             "/chart",
             get(|| async {
@@ -50,6 +135,8 @@ pub fn app_router(state: Arc<AppState>) -> Router {
                 )
             }),
         )
+        .layer(from_fn_with_state(state.clone(), authenticate))
+        .layer(axum::middleware::from_fn(request_id))
         .with_state(state);
 
     app
@@ -1,19 +1,41 @@
 use crate::app_route::SwitchAssets;
+use crate::auth::AuthPolicy;
+use crate::balance_monitor::BalanceMonitor;
+use crate::custody::Custody;
+use crate::event_log::{EVENT_CHANNEL_CAPACITY, PublishedEvent};
+use crate::graphql::{SwitchSchema, build_schema};
+use crate::idempotency::InFlightKeys;
+use crate::signer::AppSigner;
+use crate::siwe::SiweConfig;
+use crate::spending::SpendingPolicy;
+use crate::webhooks::WebhookDispatcher;
 use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, Pool};
 use dotenv::dotenv;
-use ecdsa::SigningKey;
-use ethers::core::k256::Secp256k1;
 use ethers::{prelude::*, providers::Http};
 use eyre::Report;
 use std::env;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::broadcast;
+
+pub type SwitchClient = SignerMiddleware<Provider<Http>, AppSigner>;
+pub type SwitchContract = SwitchAssets<SwitchClient>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Pool<ConnectionManager<PgConnection>>,
-    pub contract: SwitchAssets<SignerMiddleware<Provider<Http>, Wallet<SigningKey<Secp256k1>>>>,
+    pub contract: SwitchContract,
+    pub custody: Option<Custody>,
+    pub spending: SpendingPolicy,
+    pub balance: BalanceMonitor,
+    pub in_flight: InFlightKeys,
+    // Events the indexer has committed, for live subscribers
+    pub events: broadcast::Sender<PublishedEvent>,
+    pub webhooks: WebhookDispatcher,
+    pub graphql: SwitchSchema,
+    pub auth: AuthPolicy,
+    pub siwe: SiweConfig,
     // pub last_processed_block: ()
 }
 
@@ -31,7 +53,6 @@ impl AppState {
 
         //contract connection
         let rpc_url = env::var("BASE_URL")?;
-        let private_key = env::var("PRIVATE_KEY")?;
         let switch_address: Address = env::var("CONTRACT_ADDRESS")?
             .parse()
             .map_err(|_| anyhow::anyhow!("Invalid contract address"))
@@ -40,17 +61,34 @@ impl AppState {
         let provider = Provider::<Http>::try_from(&rpc_url)?.interval(Duration::from_millis(1000));
         let chain_id = provider.get_chainid().await?.as_u64();
 
-        let wallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
-        println!("Wallet address: 0x{:x}", wallet.address());
+        let signer = AppSigner::from_env(chain_id).await?;
+        println!("Wallet address: 0x{:x}", signer.address());
 
-        let eth_client = Arc::new(SignerMiddleware::new(provider, wallet.clone()));
+        let eth_client = Arc::new(SignerMiddleware::new(provider, signer));
 
         let contract = SwitchAssets::new(switch_address, eth_client.clone());
 
         let state = AppState {
             db_pool: pool,
             contract,
+            custody: Custody::from_env()?,
+            spending: SpendingPolicy::from_env()?,
+            balance: BalanceMonitor::from_env()?,
+            in_flight: InFlightKeys::default(),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            webhooks: WebhookDispatcher::from_env()?,
+            graphql: build_schema(),
+            auth: AuthPolicy::from_env()?,
+            siwe: SiweConfig::from_env()?,
         };
         Ok(state)
     }
+
+    // Same contract and RPC connection as the service wallet, signed by another key
+    pub fn contract_for(&self, wallet: LocalWallet) -> SwitchContract {
+        let client = self.contract.client();
+        let signer = AppSigner::Local(wallet).with_chain_id(client.signer().chain_id());
+        let eth_client = Arc::new(SignerMiddleware::new(client.inner().clone(), signer));
+        SwitchAssets::new(self.contract.address(), eth_client)
+    }
 }
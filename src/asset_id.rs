@@ -0,0 +1,224 @@
+use crate::address::EvmAddress;
+use crate::models::{AssetVerification, RawAsset};
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use ethers::abi::{Token, encode};
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::Write;
+use utoipa::openapi::RefOr;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+use utoipa::{PartialSchema, ToSchema};
+
+// A SwitchAssets asset id: exactly 32 bytes, stored and returned as lowercase 0x-prefixed hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct AssetId(pub H256);
+
+impl AssetId {
+    // `field` names the input in the error message
+    pub fn parse(field: &str, input: &str) -> Result<AssetId, String> {
+        let input = input.trim();
+        let digits = input
+            .strip_prefix("0x")
+            .or_else(|| input.strip_prefix("0X"))
+            .unwrap_or(input);
+        if digits.len() != 64 {
+            return Err(format!(
+                "Invalid {} length: expected 32 bytes (64 hex digits), got {} digits",
+                field,
+                digits.len()
+            ));
+        }
+        let bytes =
+            hex::decode(digits).map_err(|e| format!("Invalid {} format: {:?}", field, e))?;
+        Ok(AssetId(H256::from_slice(&bytes)))
+    }
+}
+
+impl From<H256> for AssetId {
+    fn from(asset_id: H256) -> Self {
+        AssetId(asset_id)
+    }
+}
+
+impl From<[u8; 32]> for AssetId {
+    fn from(asset_id: [u8; 32]) -> Self {
+        AssetId(H256::from(asset_id))
+    }
+}
+
+impl From<AssetId> for [u8; 32] {
+    fn from(asset_id: AssetId) -> Self {
+        asset_id.0.into()
+    }
+}
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl Serialize for AssetId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = String::deserialize(deserializer)?;
+        AssetId::parse("asset_id", &input).map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Text, Pg> for AssetId {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.to_string().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for AssetId {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let stored = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(AssetId::parse("stored asset_id", &stored)?)
+    }
+}
+
+impl PartialSchema for AssetId {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some("^(0[xX])?[0-9a-fA-F]{64}$"))
+            .description(Some(
+                "32-byte asset id as 0x-prefixed hex, returned lowercase",
+            ))
+            .examples(["0x8f1c3d1ad2e1a9f0e7b6d4c2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a3f2"])
+            .into()
+    }
+}
+
+impl ToSchema for AssetId {}
+
+// Recomputes an asset id the way SwitchAssets.registerAsset does:
+// keccak256(abi.encode(caller, block.timestamp, description))
+pub fn derive_asset_id(registrant: H160, registered_at: U256, description: &str) -> H256 {
+    H256::from(keccak256(encode(&[
+        Token::Address(registrant),
+        Token::Uint(registered_at),
+        Token::String(description.to_string()),
+    ])))
+}
+
+// Checks a stored asset against its registration data. The id commits to whoever registered
+// the asset, which is the old owner of its first transfer, or the current owner if it has
+// never moved. Both are tried, since the index may not reach back to the first transfer.
+pub fn verify_asset(asset: &RawAsset, first_old_owner: Option<&str>) -> AssetVerification {
+    let mut verification = AssetVerification {
+        asset_id: asset.asset_id.clone(),
+        owner: asset.owner.clone(),
+        registrant: None,
+        derived_asset_id: None,
+        valid: false,
+        reason: None,
+    };
+
+    let Ok(AssetId(stored_id)) = AssetId::parse("asset_id", &asset.asset_id) else {
+        verification.reason = Some("Stored asset_id is not a 32-byte hex value".to_string());
+        return verification;
+    };
+    if asset.registered_at < 0 {
+        verification.reason = Some("Stored registered_at is negative".to_string());
+        return verification;
+    }
+    let registered_at = U256::from(asset.registered_at as u64);
+
+    let candidates = first_old_owner
+        .into_iter()
+        .chain([asset.owner.as_str()])
+        .filter_map(|candidate| EvmAddress::parse("owner", candidate).ok())
+        .map(|candidate| candidate.0)
+        .collect::<Vec<_>>();
+    for registrant in &candidates {
+        let derived = derive_asset_id(*registrant, registered_at, &asset.description);
+        if verification.derived_asset_id.is_none() {
+            verification.derived_asset_id = Some(AssetId(derived).to_string());
+        }
+        if derived == stored_id {
+            verification.registrant = Some(EvmAddress(*registrant).to_string());
+            verification.derived_asset_id = Some(AssetId(derived).to_string());
+            verification.valid = true;
+            return verification;
+        }
+    }
+
+    verification.reason = Some(if candidates.is_empty() {
+        "No parseable owner to derive the asset_id from".to_string()
+    } else {
+        "asset_id does not match the stored owner, registered_at and description".to_string()
+    });
+    verification
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOWERCASE: &str = "0x8f1c3d1ad2e1a9f0e7b6d4c2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a3f2";
+
+    #[test]
+    fn parse_accepts_any_case_with_or_without_prefix() {
+        let expected = AssetId::parse("asset_id", LOWERCASE).unwrap();
+        assert_eq!(expected.to_string(), LOWERCASE);
+        for input in [
+            LOWERCASE[2..].to_string(),
+            LOWERCASE.to_uppercase(),
+            LOWERCASE.to_uppercase().replacen("0X", "0x", 1),
+            format!("  {}\n", LOWERCASE),
+        ] {
+            let parsed = AssetId::parse("asset_id", &input).unwrap();
+            assert_eq!(parsed, expected, "{}", input);
+            assert_eq!(parsed.to_string(), LOWERCASE);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_wrong_length_and_non_hex() {
+        for input in ["", "0x", &LOWERCASE[..65], &format!("{}00", LOWERCASE)] {
+            let error = AssetId::parse("asset_id", input).unwrap_err();
+            assert!(error.starts_with("Invalid asset_id length"), "{}", error);
+        }
+        let error = AssetId::parse("asset_id", &LOWERCASE.replacen('f', "g", 1)).unwrap_err();
+        assert!(error.starts_with("Invalid asset_id format"), "{}", error);
+        // A 0x inside the digits is not a prefix
+        let error = AssetId::parse("asset_id", &format!("00{}", &LOWERCASE[..62])).unwrap_err();
+        assert!(error.starts_with("Invalid asset_id format"), "{}", error);
+    }
+
+    // What SwitchAssets.registerAsset computes, keccak256(abi.encode(caller, block.timestamp,
+    // description)), for a fixed registration, worked out independently of ethers. Packing the
+    // same inputs (abi.encodePacked) would give 0xb770...6371 instead.
+    #[test]
+    fn derive_asset_id_matches_register_asset() {
+        let registrant =
+            EvmAddress::parse("registrant", "0xd8da6bf26964af9d7eed9e03e53415d37aa96045")
+                .unwrap()
+                .0;
+        let derived = derive_asset_id(
+            registrant,
+            U256::from(1755547554u64),
+            "Rolex Submariner 126610LN, serial 7XY12345",
+        );
+        assert_eq!(
+            AssetId(derived).to_string(),
+            "0x621c690c4b008867ec4e3fec2af80407953f2752e976ed8abb279de3b1e47090"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use crate::app_state::AppState;
+use crate::error::{ApiError, current_request_id};
+use crate::schema::{api_key_writes, api_keys};
+use crate::siwe::{SessionIdentity, find_session};
+use axum::extract::{FromRequestParts, OptionalFromRequestParts, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::env;
+use std::io::Write;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// Every key starts with this, so a leaked one is easy to recognise
+const KEY_PREFIX: &str = "swk_";
+// Characters of a key kept in key_prefix
+const SHOWN_PREFIX_LEN: usize = 12;
+// last_used_at is refreshed at most this often, not on every request
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+// What an API key may do. Each role includes the ones before it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(rename_all = "snake_case")]
+#[diesel(sql_type = Text)]
+pub enum Role {
+    ReadOnly,
+    Writer,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read_only",
+            Role::Writer => "writer",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for Role {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "read_only" => Ok(Role::ReadOnly),
+            "writer" => Ok(Role::Writer),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("Unknown role: {}", other).into()),
+        }
+    }
+}
+
+// Write and admin endpoints always need a key of the matching role. Configured from the
+// environment:
+// - API_REQUIRE_KEY_FOR_READS: when true, read endpoints need a key too (any role), default
+//   false
+// - API_BOOTSTRAP_ADMIN_KEY: an admin key to store at startup, for creating the first keys
+//   through the API. It is stored hashed like any other and can be revoked once real admin
+//   keys exist.
+#[derive(Clone, Default)]
+pub struct AuthPolicy {
+    require_key_for_reads: bool,
+    bootstrap_key: Option<String>,
+}
+
+impl AuthPolicy {
+    pub fn from_env() -> Result<AuthPolicy> {
+        let require_key_for_reads = match env::var("API_REQUIRE_KEY_FOR_READS") {
+            Ok(value) => value
+                .trim()
+                .parse::<bool>()
+                .map_err(|e| eyre::eyre!("Invalid API_REQUIRE_KEY_FOR_READS: {}", e))?,
+            Err(_) => false,
+        };
+        let bootstrap_key = env::var("API_BOOTSTRAP_ADMIN_KEY")
+            .ok()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty());
+
+        Ok(AuthPolicy {
+            require_key_for_reads,
+            bootstrap_key,
+        })
+    }
+}
+
+// The API key a request authenticated with, put in the request extensions by authenticate
+#[derive(Debug, Clone)]
+pub struct ApiKeyIdentity {
+    pub id: i32,
+    pub name: String,
+    pub role: Role,
+}
+
+// Handlers and extractors that act on behalf of the caller take the key from here, never from
+// a header the caller could set to anything
+impl<S: Send + Sync> FromRequestParts<S> for ApiKeyIdentity {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ApiKeyIdentity>()
+            .cloned()
+            .ok_or_else(|| {
+                ApiError::unauthorized(format!(
+                    "An API key is required in the {} header",
+                    API_KEY_HEADER
+                ))
+            })
+    }
+}
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ApiKeyIdentity {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<ApiKeyIdentity>().cloned())
+    }
+}
+
+pub fn generate_key() -> String {
+    format!("{}{}", KEY_PREFIX, hex::encode(rand::random::<[u8; 32]>()))
+}
+
+// Keys are 256 random bits, so a plain SHA-256 is enough to make the stored hash useless to
+// whoever reads it, and cheap enough to compute on every request
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(SHOWN_PREFIX_LEN).collect()
+}
+
+// Stores API_BOOTSTRAP_ADMIN_KEY, unless it is already stored (revoked or not)
+pub fn bootstrap_admin_key(state: &AppState) -> Result<()> {
+    let Some(key) = &state.auth.bootstrap_key else {
+        return Ok(());
+    };
+    let conn = &mut state.db_pool.get()?;
+    let inserted = diesel::insert_into(api_keys::table)
+        .values((
+            api_keys::name.eq("bootstrap"),
+            api_keys::key_prefix.eq(key_prefix(key)),
+            api_keys::key_hash.eq(hash_key(key)),
+            api_keys::role.eq(Role::Admin),
+            api_keys::created_at.eq(chrono::Utc::now().timestamp()),
+        ))
+        .on_conflict(api_keys::key_hash)
+        .do_nothing()
+        .execute(conn)?;
+    if inserted > 0 {
+        println!("Stored API_BOOTSTRAP_ADMIN_KEY as an admin API key");
+    }
+    Ok(())
+}
+
+// Resolves the x-api-key header, if any, to its key, and an Authorization: Bearer session token
+// to its Sign-In with Ethereum session. A key or token that is unknown, revoked or expired is
+// refused outright rather than treated as none.
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if let Some(value) = request.headers().get(API_KEY_HEADER) {
+        let key = value
+            .to_str()
+            .map_err(|_| ApiError::unauthorized("Invalid API key"))?
+            .trim();
+
+        let conn = &mut state.db_pool.get()?;
+        let (id, name, role) = api_keys::table
+            .filter(api_keys::key_hash.eq(hash_key(key)))
+            .filter(api_keys::revoked_at.is_null())
+            .select((api_keys::id, api_keys::name, api_keys::role))
+            .first::<(i32, String, Role)>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::unauthorized("Unknown or revoked API key"))?;
+
+        let now = chrono::Utc::now().timestamp();
+        diesel::update(
+            api_keys::table.find(id).filter(
+                api_keys::last_used_at
+                    .is_null()
+                    .or(api_keys::last_used_at.lt(now - LAST_USED_RESOLUTION_SECS)),
+            ),
+        )
+        .set(api_keys::last_used_at.eq(now))
+        .execute(conn)?;
+
+        request
+            .extensions_mut()
+            .insert(ApiKeyIdentity { id, name, role });
+    }
+
+    if let Some(value) = request.headers().get(AUTHORIZATION) {
+        let token = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().strip_prefix("Bearer "))
+            .ok_or_else(|| ApiError::unauthorized("Invalid Authorization header"))?
+            .trim();
+
+        let conn = &mut state.db_pool.get()?;
+        let session = find_session(conn, token)?
+            .ok_or_else(|| ApiError::unauthorized("Unknown, expired or revoked session"))?;
+        request.extensions_mut().insert(session);
+    }
+    Ok(next.run(request).await)
+}
+
+// The role a group of routes requires, as the state of its authorize layer
+#[derive(Clone)]
+pub struct RequireRole {
+    state: Arc<AppState>,
+    role: Role,
+}
+
+impl RequireRole {
+    pub fn new(state: &Arc<AppState>, role: Role) -> Self {
+        RequireRole {
+            state: state.clone(),
+            role,
+        }
+    }
+}
+
+// Refuses requests whose key (from authenticate) lacks the required role, and records every
+// request a key makes to a write or admin route. A signed-in wallet may use read routes even
+// when they need a key, but never write or admin ones.
+pub async fn authorize(
+    State(required): State<RequireRole>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let identity = request.extensions().get::<ApiKeyIdentity>().cloned();
+    let signed_in = request.extensions().get::<SessionIdentity>().is_some();
+    let identity = match identity {
+        None if required.role == Role::ReadOnly
+            && (signed_in || !required.state.auth.require_key_for_reads) =>
+        {
+            return Ok(next.run(request).await);
+        }
+        None => {
+            return Err(ApiError::unauthorized(format!(
+                "An API key with the {} role is required in the {} header",
+                required.role.as_str(),
+                API_KEY_HEADER
+            )));
+        }
+        Some(identity) if identity.role < required.role => {
+            return Err(ApiError::forbidden(format!(
+                "API key {} has the {} role, this endpoint needs {}",
+                identity.name,
+                identity.role.as_str(),
+                required.role.as_str()
+            )));
+        }
+        Some(identity) => identity,
+    };
+    if required.role == Role::ReadOnly {
+        return Ok(next.run(request).await);
+    }
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+
+    // The request has already been served, so failing to log it must not change the response
+    let status = response.status().as_u16() as i32;
+    if let Err(e) = record_write(&required.state, identity.id, method, path, status) {
+        eprintln!("Failed to record write by API key {}: {:?}", identity.id, e);
+    }
+    Ok(response)
+}
+
+fn record_write(
+    state: &AppState,
+    api_key_id: i32,
+    method: String,
+    path: String,
+    status: i32,
+) -> Result<(), ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    diesel::insert_into(api_key_writes::table)
+        .values((
+            api_key_writes::api_key_id.eq(api_key_id),
+            api_key_writes::method.eq(method),
+            api_key_writes::path.eq(path),
+            api_key_writes::status.eq(status),
+            api_key_writes::request_id.eq(current_request_id()),
+            api_key_writes::created_at.eq(chrono::Utc::now().timestamp()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
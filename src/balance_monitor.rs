@@ -0,0 +1,191 @@
+use crate::address::EvmAddress;
+use crate::app_state::AppState;
+use crate::models::WalletBalanceResponse;
+use crate::schema::assets;
+use diesel::prelude::*;
+use ethers::prelude::*;
+use eyre::Result;
+use serde_json::json;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+// Fallback gas figures for when a live estimate is not possible, e.g. a transfer while the
+// service wallet owns no assets to estimate against
+const REGISTER_GAS_FALLBACK: u64 = 200_000;
+const TRANSFER_GAS_FALLBACK: u64 = 100_000;
+
+// Polls the service wallet balance and works out how many writes it can still pay for.
+// Configured from the environment:
+// - BALANCE_POLL_SECS: seconds between polls, default 60
+// - BALANCE_ALERT_MIN_OPS: alert when fewer registrations or transfers than this remain, default 50
+// - BALANCE_ALERT_WEBHOOK_URL: POSTed a JSON alert when funds run low or recover, otherwise
+//   the alert is only logged
+#[derive(Clone)]
+pub struct BalanceMonitor {
+    poll_interval: Duration,
+    min_ops: u64,
+    webhook_url: Option<String>,
+    http: reqwest::Client,
+    latest: Arc<RwLock<Option<WalletBalanceResponse>>>,
+}
+
+impl BalanceMonitor {
+    pub fn from_env() -> Result<BalanceMonitor> {
+        let poll_secs = match env::var("BALANCE_POLL_SECS") {
+            Ok(value) => value
+                .parse::<u64>()
+                .map_err(|e| eyre::eyre!("Invalid BALANCE_POLL_SECS: {}", e))?,
+            Err(_) => 60,
+        };
+        let min_ops = match env::var("BALANCE_ALERT_MIN_OPS") {
+            Ok(value) => value
+                .parse::<u64>()
+                .map_err(|e| eyre::eyre!("Invalid BALANCE_ALERT_MIN_OPS: {}", e))?,
+            Err(_) => 50,
+        };
+
+        Ok(BalanceMonitor {
+            poll_interval: Duration::from_secs(poll_secs.max(1)),
+            min_ops,
+            webhook_url: env::var("BALANCE_ALERT_WEBHOOK_URL").ok(),
+            http: reqwest::Client::new(),
+            latest: Arc::new(RwLock::new(None)),
+        })
+    }
+
+    pub async fn latest(&self) -> Option<WalletBalanceResponse> {
+        self.latest.read().await.clone()
+    }
+}
+
+pub async fn monitor_balance(state: Arc<AppState>) -> Result<()> {
+    let monitor = &state.balance;
+    let mut interval = tokio::time::interval(monitor.poll_interval);
+
+    loop {
+        interval.tick().await;
+
+        let reading = match check_balance(&state).await {
+            Ok(reading) => reading,
+            Err(e) => {
+                eprintln!("Balance monitor error: {:?}", e);
+                continue;
+            }
+        };
+
+        // Only alert on the transition, not on every poll while funds stay low
+        let was_low = monitor
+            .latest()
+            .await
+            .is_some_and(|previous| previous.low_funds);
+        if reading.low_funds != was_low {
+            alert(monitor, &reading).await;
+        }
+
+        *monitor.latest.write().await = Some(reading);
+    }
+}
+
+async fn check_balance(state: &AppState) -> Result<WalletBalanceResponse> {
+    let contract = &state.contract;
+    let client = contract.client();
+    let wallet_address = client.address();
+
+    let balance = client.get_balance(wallet_address, None).await?;
+    let gas_price = client
+        .get_gas_price()
+        .await
+        .unwrap_or(U256::from(2_000_000_000u64));
+
+    let register_gas = contract
+        .register_asset("balance monitor".to_string())
+        .estimate_gas()
+        .await
+        .unwrap_or(U256::from(REGISTER_GAS_FALLBACK));
+    let transfer_gas = estimate_transfer_gas(state, wallet_address)
+        .await
+        .unwrap_or(U256::from(TRANSFER_GAS_FALLBACK));
+
+    // Same 120% buffer the write endpoints require the balance to cover
+    let register_cost = register_gas * 120 / 100 * gas_price;
+    let transfer_cost = transfer_gas * 120 / 100 * gas_price;
+    let registers_remaining = remaining(balance, register_cost);
+    let transfers_remaining = remaining(balance, transfer_cost);
+
+    Ok(WalletBalanceResponse {
+        address: EvmAddress(wallet_address),
+        balance_wei: balance.to_string(),
+        gas_price_wei: gas_price.to_string(),
+        register_cost_wei: register_cost.to_string(),
+        transfer_cost_wei: transfer_cost.to_string(),
+        registers_remaining,
+        transfers_remaining,
+        low_funds: registers_remaining.min(transfers_remaining) < state.balance.min_ops,
+        checked_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+// A transfer can only be estimated for an asset the wallet actually owns
+async fn estimate_transfer_gas(state: &AppState, wallet_address: H160) -> Option<U256> {
+    let conn = &mut state.db_pool.get().ok()?;
+    let asset_id = assets::table
+        .filter(assets::owner.eq(EvmAddress(wallet_address).to_string()))
+        .select(assets::asset_id)
+        .first::<String>(conn)
+        .ok()?;
+    let asset_id = hex::decode(asset_id.strip_prefix("0x").unwrap_or(&asset_id)).ok()?;
+    let asset_id = <[u8; 32]>::try_from(asset_id.as_slice()).ok()?;
+
+    state
+        .contract
+        .transfer_asset(asset_id, H160::from_low_u64_be(1))
+        .estimate_gas()
+        .await
+        .ok()
+}
+
+fn remaining(balance: U256, cost: U256) -> u64 {
+    if cost.is_zero() {
+        return u64::MAX;
+    }
+    (balance / cost).min(U256::from(u64::MAX)).as_u64()
+}
+
+async fn alert(monitor: &BalanceMonitor, reading: &WalletBalanceResponse) {
+    let message = if reading.low_funds {
+        format!(
+            "⚠️  Service wallet {} is low on funds: {} wei left, enough for {} registrations / {} transfers",
+            reading.address,
+            reading.balance_wei,
+            reading.registers_remaining,
+            reading.transfers_remaining
+        )
+    } else {
+        format!(
+            "✅ Service wallet {} is funded again: {} wei",
+            reading.address, reading.balance_wei
+        )
+    };
+    eprintln!("{}", message);
+
+    let Some(url) = &monitor.webhook_url else {
+        return;
+    };
+    let body = json!({
+        "event": if reading.low_funds { "wallet.low_funds" } else { "wallet.funds_recovered" },
+        "message": message,
+        "wallet": reading,
+    });
+    if let Err(e) = monitor
+        .http
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+    {
+        eprintln!("Balance alert webhook error: {:?}", e);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::address::EvmAddress;
+use crate::error::ApiError;
+use crate::models::{BatchItemResult, BatchJob, BatchJobItem, BatchJobResponse};
+use crate::schema::{batch_job_items, batch_jobs};
+use crate::spending::release;
+use diesel::prelude::*;
+use ethers::prelude::*;
+
+pub const STATUS_QUEUED: &str = "queued";
+pub const STATUS_SUBMITTED: &str = "submitted";
+pub const STATUS_CONFIRMED: &str = "confirmed";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_REJECTED: &str = "rejected";
+
+pub const MAX_BATCH_SIZE: usize = 500;
+
+// What the client asked for in one batch slot, independent of how far it got
+#[derive(Clone)]
+pub struct NewBatchItem {
+    pub description: Option<String>,
+    pub asset_id: Option<String>,
+    pub new_owner: Option<String>,
+}
+
+impl NewBatchItem {
+    pub fn register(description: &str) -> Self {
+        Self {
+            description: Some(description.to_string()),
+            asset_id: None,
+            new_owner: None,
+        }
+    }
+
+    pub fn transfer(asset_id: &str, new_owner: &str) -> Self {
+        Self {
+            description: None,
+            asset_id: Some(asset_id.to_string()),
+            new_owner: Some(new_owner.to_string()),
+        }
+    }
+
+    // Register slots get their asset_id filled in once mined, so only compare what was sent
+    fn matches(&self, item: &BatchJobItem) -> bool {
+        self.description == item.description
+            && self.new_owner == item.new_owner
+            && (self.asset_id.is_none() || self.asset_id == item.asset_id)
+    }
+}
+
+// Where a previously broadcast batch transaction stands right now
+pub enum Settlement {
+    Mined(Box<TransactionReceipt>),
+    Pending,
+    Dropped,
+}
+
+// Looks up the client's job for this idempotency key, creating it (and one queued item per
+// entry) on first use. Keys are scoped to the client, so two clients picking the same key get
+// separate jobs. A replayed key must describe exactly the same batch, sent by the same wallet.
+pub fn load_or_create_job(
+    conn: &mut PgConnection,
+    client: &str,
+    idempotency_key: &str,
+    kind: &str,
+    signer: H160,
+    new_items: &[NewBatchItem],
+) -> Result<BatchJob, ApiError> {
+    let signer = EvmAddress(signer).to_string();
+    conn.transaction::<_, ApiError, _>(|conn| {
+        // Claiming the key with the insert itself, rather than checking for it first, means two
+        // instances racing on the same key cannot both create a job: the loser waits for the
+        // winner to commit, inserts nothing, and reads the winner's job below.
+        let created = diesel::insert_into(batch_jobs::table)
+            .values((
+                batch_jobs::idempotency_key.eq(idempotency_key),
+                batch_jobs::kind.eq(kind),
+                batch_jobs::created_at.eq(chrono::Utc::now().timestamp()),
+                batch_jobs::signer.eq(Some(&signer)),
+                batch_jobs::client.eq(client),
+            ))
+            .on_conflict((batch_jobs::client, batch_jobs::idempotency_key))
+            .do_nothing()
+            .returning(BatchJob::as_returning())
+            .get_result::<BatchJob>(conn)
+            .optional()?;
+
+        if let Some(job) = created {
+            let rows = new_items
+                .iter()
+                .enumerate()
+                .map(|(index, new_item)| {
+                    (
+                        batch_job_items::job_id.eq(job.id),
+                        batch_job_items::item_index.eq(index as i32),
+                        batch_job_items::description.eq(new_item.description.clone()),
+                        batch_job_items::asset_id.eq(new_item.asset_id.clone()),
+                        batch_job_items::new_owner.eq(new_item.new_owner.clone()),
+                        batch_job_items::status.eq(STATUS_QUEUED),
+                    )
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(batch_job_items::table)
+                .values(&rows)
+                .execute(conn)?;
+            return Ok(job);
+        }
+
+        let job = batch_jobs::table
+            .filter(batch_jobs::client.eq(client))
+            .filter(batch_jobs::idempotency_key.eq(idempotency_key))
+            .select(BatchJob::as_select())
+            .first::<BatchJob>(conn)?;
+        let items = load_items(conn, job.id)?;
+        // Jobs from before signers were recorded can only be matched on their items
+        let same_batch = job.kind == kind
+            && job
+                .signer
+                .as_ref()
+                .is_none_or(|job_signer| *job_signer == signer)
+            && items.len() == new_items.len()
+            && items
+                .iter()
+                .zip(new_items)
+                .all(|(item, new_item)| new_item.matches(item));
+        if !same_batch {
+            return Err(ApiError::conflict(format!(
+                "Idempotency key {} was already used for a different batch",
+                idempotency_key
+            )));
+        }
+        Ok(job)
+    })
+}
+
+// A keyed single write that already ended in failure answers a replay the way it answered the
+// first attempt, rather than being sent again
+pub fn replayed_failure(item: &BatchJobItem) -> Option<ApiError> {
+    let error = item.error.clone().unwrap_or_default();
+    match item.status.as_str() {
+        STATUS_FAILED => Some(ApiError::ContractReverted(error)),
+        STATUS_REJECTED => Some(ApiError::bad_request(error)),
+        _ => None,
+    }
+}
+
+pub fn load_items(conn: &mut PgConnection, job_id: i32) -> Result<Vec<BatchJobItem>, ApiError> {
+    batch_job_items::table
+        .filter(batch_job_items::job_id.eq(job_id))
+        .order(batch_job_items::item_index.asc())
+        .select(BatchJobItem::as_select())
+        .load::<BatchJobItem>(conn)
+        .map_err(ApiError::from)
+}
+
+// The only item of a single-write job (a keyed register/transfer, or a relay)
+pub fn load_single_item(conn: &mut PgConnection, job_id: i32) -> Result<BatchJobItem, ApiError> {
+    load_items(conn, job_id)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ApiError::internal(format!("Job {} has no items", job_id)))
+}
+
+pub fn mark_submitted(
+    conn: &mut PgConnection,
+    item_id: i32,
+    txn_hash: H256,
+) -> Result<(), ApiError> {
+    update_item(
+        conn,
+        item_id,
+        STATUS_SUBMITTED,
+        Some(format!("0x{}", hex::encode(txn_hash))),
+        None,
+    )
+}
+
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    item: &BatchJobItem,
+    error: String,
+) -> Result<(), ApiError> {
+    eprintln!("Batch item {} failed: {}", item.item_index, error);
+    update_item(
+        conn,
+        item.id,
+        STATUS_FAILED,
+        item.txn_hash.clone(),
+        Some(error),
+    )
+}
+
+pub fn mark_rejected(
+    conn: &mut PgConnection,
+    item: &BatchJobItem,
+    reason: String,
+) -> Result<(), ApiError> {
+    eprintln!("Batch item {} rejected: {}", item.item_index, reason);
+    update_item(conn, item.id, STATUS_REJECTED, None, Some(reason))
+}
+
+pub fn mark_confirmed(
+    conn: &mut PgConnection,
+    item_id: i32,
+    asset_id: &str,
+) -> Result<(), ApiError> {
+    diesel::update(batch_job_items::table.find(item_id))
+        .set((
+            batch_job_items::status.eq(STATUS_CONFIRMED),
+            batch_job_items::asset_id.eq(Some(asset_id)),
+            batch_job_items::error.eq(None::<String>),
+        ))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(ApiError::from)
+}
+
+pub fn mark_requeued(conn: &mut PgConnection, item_id: i32) -> Result<(), ApiError> {
+    update_item(conn, item_id, STATUS_QUEUED, None, None)
+}
+
+// A batch transaction that never made it into a block paid nothing: its spend is released and
+// the item queued to be sent again on the next run
+pub fn requeue_dropped(
+    conn: &mut PgConnection,
+    item: &BatchJobItem,
+    txn_hash: H256,
+) -> Result<(), ApiError> {
+    eprintln!(
+        "Batch item {} tx {:?} was dropped, requeueing",
+        item.item_index, txn_hash
+    );
+    release(conn, txn_hash)?;
+    mark_requeued(conn, item.id)
+}
+
+fn update_item(
+    conn: &mut PgConnection,
+    item_id: i32,
+    status: &str,
+    txn_hash: Option<String>,
+    error: Option<String>,
+) -> Result<(), ApiError> {
+    diesel::update(batch_job_items::table.find(item_id))
+        .set((
+            batch_job_items::status.eq(status),
+            batch_job_items::txn_hash.eq(txn_hash),
+            batch_job_items::error.eq(error),
+        ))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(ApiError::from)
+}
+
+pub fn job_response(conn: &mut PgConnection, job: &BatchJob) -> Result<BatchJobResponse, ApiError> {
+    let items = load_items(conn, job.id)?;
+    Ok(BatchJobResponse {
+        idempotency_key: job.idempotency_key.clone(),
+        kind: job.kind.clone(),
+        created_at: job.created_at,
+        signer: job.signer.clone(),
+        items: items.into_iter().map(BatchItemResult::from).collect(),
+    })
+}
+
+pub fn submitted_hash(item: &BatchJobItem) -> Result<H256, ApiError> {
+    item.txn_hash
+        .as_deref()
+        .unwrap_or_default()
+        .parse::<H256>()
+        .map_err(|e| {
+            ApiError::internal(format!(
+                "Invalid stored txn_hash for batch item {}: {:?}",
+                item.id, e
+            ))
+        })
+}
+
+pub async fn check_submitted<M: Middleware>(
+    client: &M,
+    txn_hash: H256,
+) -> Result<Settlement, ApiError> {
+    let receipt = client
+        .get_transaction_receipt(txn_hash)
+        .await
+        .map_err(ApiError::provider)?;
+    if let Some(receipt) = receipt {
+        return Ok(Settlement::Mined(Box::new(receipt)));
+    }
+
+    let known = client
+        .get_transaction(txn_hash)
+        .await
+        .map_err(ApiError::provider)?;
+    Ok(match known {
+        Some(_) => Settlement::Pending,
+        None => Settlement::Dropped,
+    })
+}
+
+pub async fn await_receipt<M: Middleware>(
+    client: &M,
+    txn_hash: H256,
+) -> Result<Option<TransactionReceipt>, ApiError> {
+    Ok(PendingTransaction::new(txn_hash, client.provider()).await?)
+}
@@ -0,0 +1,112 @@
+use crate::address::EvmAddress;
+use crate::app_state::{AppState, SwitchClient, SwitchContract};
+use crate::contract_calls::transfer_asset::validate_transfer;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiJson;
+use crate::models::{
+    ApiResponse, BuildRegisterInput, BuildTransferInput, UnsignedTransactionResponse,
+};
+use axum::{Json, extract::State};
+use ethers::prelude::*;
+use std::sync::Arc;
+
+#[utoipa::path(
+    post,
+    path = "/contract/build/register",
+    request_body(content = BuildRegisterInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Unsigned registerAsset transaction ready for the wallet to sign", body = ApiResponse<UnsignedTransactionResponse>),
+        (status = 400, description = "Invalid sender or the call would revert", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn build_register_transaction(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<BuildRegisterInput>,
+) -> Result<Json<ApiResponse<UnsignedTransactionResponse>>, ApiError> {
+    let from = parse_sender(input.from)?;
+
+    let call = state.contract.register_asset(input.description).from(from);
+    let unsigned = build_unsigned(&state.contract, call, from).await?;
+
+    Ok(Json(ApiResponse::new(unsigned)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/contract/build/transfer",
+    request_body(content = BuildTransferInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Unsigned transferAsset transaction ready for the wallet to sign", body = ApiResponse<UnsignedTransactionResponse>),
+        (status = 400, description = "Invalid input, or the sender may not transfer this asset", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn build_transfer_transaction(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<BuildTransferInput>,
+) -> Result<Json<ApiResponse<UnsignedTransactionResponse>>, ApiError> {
+    let from = parse_sender(input.from)?;
+    let (asset_id, new_owner) = (input.asset_id.0, input.new_owner.0);
+
+    validate_transfer(&state.contract, from, asset_id, new_owner).await?;
+
+    let call = state
+        .contract
+        .transfer_asset(<[u8; 32]>::from(asset_id), new_owner)
+        .from(from);
+    let unsigned = build_unsigned(&state.contract, call, from).await?;
+
+    Ok(Json(ApiResponse::new(unsigned)))
+}
+
+// The contract's addressZeroCheck modifier rejects a zero msg.sender, so do we
+fn parse_sender(from: EvmAddress) -> Result<H160, ApiError> {
+    if from.is_zero() {
+        return Err(ApiError::bad_request(
+            "ADDRESS_ZERO: sender cannot be the zero address",
+        ));
+    }
+    Ok(from.0)
+}
+
+async fn build_unsigned(
+    contract: &SwitchContract,
+    call: ContractCall<SwitchClient, ()>,
+    from: H160,
+) -> Result<UnsignedTransactionResponse, ApiError> {
+    let client = contract.client();
+
+    // Estimating as the sender doubles as a dry run: anything that would revert fails here
+    let gas_estimate = call.estimate_gas().await.map_err(|e| {
+        ApiError::bad_request(format!("Gas estimation error for 0x{:x}: {:?}", from, e))
+    })?;
+    let gas_limit = gas_estimate * 120 / 100; // 120% buffer
+
+    let (max_fee_per_gas, max_priority_fee_per_gas) = client
+        .estimate_eip1559_fees(None)
+        .await
+        .map_err(ApiError::provider)?;
+    let nonce = client
+        .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+        .await
+        .map_err(ApiError::provider)?;
+    let data = call
+        .calldata()
+        .ok_or_else(|| ApiError::internal("Contract call has no calldata"))?;
+
+    Ok(UnsignedTransactionResponse {
+        from: EvmAddress(from),
+        to: EvmAddress(contract.address()),
+        data: format!("0x{}", hex::encode(data)),
+        value: "0x0".to_string(),
+        chain_id: client.signer().chain_id(),
+        nonce: format!("0x{:x}", nonce),
+        gas: format!("0x{:x}", gas_limit),
+        max_fee_per_gas: format!("0x{:x}", max_fee_per_gas),
+        max_priority_fee_per_gas: format!("0x{:x}", max_priority_fee_per_gas),
+        tx_type: "0x2".to_string(),
+    })
+}
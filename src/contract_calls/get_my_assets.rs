@@ -1,34 +1,52 @@
-use crate::app_state::AppState;
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::custody::{ACTING_USER_HEADER, ActingContract};
+use crate::error::{ApiError, ErrorResponse};
 use crate::models::{ApiResponse, Asset as DbAsset};
+use crate::siwe::SignedIn;
 use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
-use std::sync::Arc;
+use axum::http::HeaderMap;
 
 #[utoipa::path(
     get,
     path = "/contract/get_my_assets",
+    params(
+        ("x-switch-user" = Option<String>, Header, description = "Custodial user to act as, defaults to the service wallet, and not allowed when signed in")
+    ),
     responses(
-        (status = 200, description = "User's assets retrieved successfully", body = ApiResponse<Vec<DbAsset>>),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "Assets held by the signed-in wallet, or else the acting user's wallet, retrieved successfully", body = ApiResponse<Vec<DbAsset>>),
+        (status = 400, description = "Both signed in and acting as a custodial user", body = ErrorResponse),
+        (status = 401, description = "Unknown, expired or revoked session, or x-switch-user sent without an API key", body = ErrorResponse),
+        (status = 403, description = "API key did not create the x-switch-user user", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 
 pub async fn get_my_assets(
-    State(state): State<Arc<AppState>>,
-) -> eyre::Result<Json<Vec<crate::models::Asset>>, StatusCode> {
-    let contract = state.contract.clone();
-    let assets_tuple = contract.get_my_assets().call().await.map_err(|e| {
-        eprintln!("getMyAssets call error: {:?}", e.to_string());
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    signed_in: Option<SignedIn>,
+    headers: HeaderMap,
+    ActingContract(contract): ActingContract,
+) -> eyre::Result<Json<Vec<crate::models::Asset>>, ApiError> {
+    // "My" is the signed-in wallet when there is one. The contract reads msg.sender, which a
+    // call can set to any address.
+    let call = match signed_in {
+        Some(_) if headers.contains_key(ACTING_USER_HEADER) => {
+            return Err(ApiError::bad_request(format!(
+                "Send either a session token or {}, not both",
+                ACTING_USER_HEADER
+            )));
+        }
+        Some(SignedIn(session)) => contract.get_my_assets().from(session.address.0),
+        None => contract.get_my_assets(),
+    };
+    let assets_tuple = call.call().await?;
 
     let db_assets = assets_tuple
         .into_iter()
         .map(|asset| crate::models::Asset {
-            asset_id: format!("0x{}", hex::encode(asset.asset_id)),
-            owner: format!("0x{}", hex::encode(asset.asset_owner)),
+            asset_id: AssetId::from(asset.asset_id),
+            owner: EvmAddress(asset.asset_owner),
             description: asset.description.to_string(),
             registered_at: asset.registered_at.as_u64() as i64,
         })
@@ -0,0 +1,80 @@
+use crate::address::EvmAddress;
+use crate::app_state::AppState;
+use crate::asset_id::AssetId;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiPath;
+use crate::models::{ApiResponse, Asset as DbAsset, OwnerHoldingsResponse};
+use crate::schema::assets;
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[utoipa::path(
+    get,
+    path = "/contract/assets/owner/{address}",
+    params(("address" = EvmAddress, Path, description = "Owner address")),
+    responses(
+        (status = 200, description = "Live on-chain holdings of the address, diffed against the indexed assets", body = ApiResponse<OwnerHoldingsResponse>),
+        (status = 400, description = "Invalid or zero address", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_owner_assets(
+    ApiPath(owner): ApiPath<EvmAddress>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<OwnerHoldingsResponse>>, ApiError> {
+    if owner.is_zero() {
+        return Err(ApiError::bad_request(
+            "ADDRESS_ZERO: cannot list holdings of the zero address",
+        ));
+    }
+
+    // getMyAssets lists msg.sender's assets, so an eth_call from the owner lists theirs
+    let on_chain = state
+        .contract
+        .get_my_assets()
+        .from(owner.0)
+        .call()
+        .await?
+        .into_iter()
+        .map(|asset| DbAsset {
+            asset_id: AssetId::from(asset.asset_id),
+            owner: EvmAddress(asset.asset_owner),
+            description: asset.description.to_string(),
+            registered_at: asset.registered_at.as_u64() as i64,
+        })
+        .collect::<Vec<_>>();
+
+    let conn = &mut state.db_pool.get()?;
+    let indexed = assets::table
+        .filter(assets::owner.eq(owner))
+        .select(assets::asset_id)
+        .load::<AssetId>(conn)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let on_chain_ids = on_chain
+        .iter()
+        .map(|asset| asset.asset_id)
+        .collect::<HashSet<_>>();
+    let mut missing_from_index = on_chain_ids
+        .difference(&indexed)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut stale_in_index = indexed
+        .difference(&on_chain_ids)
+        .cloned()
+        .collect::<Vec<_>>();
+    missing_from_index.sort();
+    stale_in_index.sort();
+
+    Ok(Json(ApiResponse::new(OwnerHoldingsResponse {
+        address: owner,
+        in_sync: missing_from_index.is_empty() && stale_in_index.is_empty(),
+        assets: on_chain,
+        missing_from_index,
+        stale_in_index,
+    })))
+}
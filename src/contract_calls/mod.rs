@@ -2,4 +2,10 @@ pub mod register_asset;
 pub mod get_asset;
 pub mod get_all_contract_assets;
 pub mod get_my_assets;
-pub mod transfer_asset;
\ No newline at end of file
+pub mod transfer_asset;
+pub mod batch;
+pub mod register_batch;
+pub mod transfer_batch;
+pub mod build_transaction;
+pub mod relay;
+pub mod get_owner_assets;
@@ -1,10 +1,25 @@
+use crate::address::EvmAddress;
+use crate::error::{ApiError, ErrorResponse};
 use std::sync::Arc;
 // use crate::state::AppState;
 use crate::app_route::AssetRegisteredFilter;
-use crate::app_state::AppState;
-use crate::models::{Asset as DbAsset, AssetRegisteredResponse, RegisterAssetInput};
+use crate::app_state::{AppState, SwitchContract};
+use crate::asset_id::AssetId;
+use crate::contract_calls::batch::{
+    NewBatchItem, STATUS_CONFIRMED, STATUS_SUBMITTED, await_receipt, load_or_create_job,
+    load_single_item, mark_confirmed, mark_failed, mark_requeued, mark_submitted, replayed_failure,
+    submitted_hash,
+};
+use crate::contract_calls::register_batch::{BATCH_KIND_REGISTER, finalize_registration};
+use crate::custody::ActingContract;
+use crate::event_log::{NewAssetEvent, record_event};
+use crate::extract::ApiJson;
+use crate::idempotency::IdempotencyKey;
+use crate::models::{Asset as DbAsset, AssetRegisteredResponse, BatchJobItem, RegisterAssetInput};
 use crate::schema::assets;
-use axum::{Json, extract::State, http::StatusCode};
+use crate::spending::{ApiClient, release, settle};
+use crate::transactions::record_sent;
+use axum::{Json, extract::State};
 use diesel::prelude::*;
 use ethabi::RawLog;
 use ethers::prelude::*;
@@ -15,28 +30,77 @@ use utoipa::ToSchema;
     post,
     path = "/contract/register",
     request_body(content = RegisterAssetInput, content_type = "application/json"),
+    params(
+        ("x-switch-user" = Option<String>, Header, description = "Custodial user to act as, defaults to the service wallet"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the original registration instead of registering again")
+    ),
     responses(
         (status = 200, description = "Asset registered successfully", body = String),
-        (status = 400, description = "Transaction failed"),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Transaction failed", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role, or did not create the x-switch-user user", body = ErrorResponse),
+        (status = 409, description = "Idempotency key already used for a different request, or still in progress", body = ErrorResponse),
+        (status = 429, description = "Wallet spending limit or client quota exceeded", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 pub async fn register_asset(
     State(state): State<Arc<AppState>>,
-    Json(input): Json<RegisterAssetInput>,
-) -> Result<Json<String>, StatusCode> {
-    let contract = state.contract.clone();
-
+    ActingContract(contract): ActingContract,
+    ApiClient(api_client): ApiClient,
+    IdempotencyKey(idempotency_key): IdempotencyKey,
+    ApiJson(input): ApiJson<RegisterAssetInput>,
+) -> Result<Json<String>, ApiError> {
     let wallet_address = contract.client().address();
+
+    // Connections are only checked out for each database step and dropped before anything
+    // waits on the chain, which can take many seconds, so slow writes can't drain the pool.
+
+    // A keyed registration is tracked as a one-item job, so a retry after a timeout gets the
+    // asset the first attempt registered rather than a second asset
+    let _in_flight = idempotency_key
+        .as_deref()
+        .map(|key| state.in_flight.claim(&api_client, key))
+        .transpose()?;
+    let mut job_item = None;
+    if let Some(key) = &idempotency_key {
+        let (job, mut item) = {
+            let conn = &mut state.db_pool.get()?;
+            let job = load_or_create_job(
+                conn,
+                &api_client,
+                key,
+                BATCH_KIND_REGISTER,
+                wallet_address,
+                &[NewBatchItem::register(&input.description)],
+            )?;
+            let item = load_single_item(conn, job.id)?;
+            (job, item)
+        };
+        if item.status == STATUS_SUBMITTED {
+            resume_registration(&state, &contract, &item).await?;
+            let conn = &mut state.db_pool.get()?;
+            item = load_single_item(conn, job.id)?;
+        }
+        if item.status == STATUS_CONFIRMED {
+            return Ok(Json(format!(
+                "Asset ID: {}, Owner: 0x{:x}",
+                item.asset_id.unwrap_or_default(),
+                wallet_address
+            )));
+        }
+        if let Some(error) = replayed_failure(&item) {
+            return Err(error);
+        }
+        job_item = Some(item);
+    }
+
     let balance = contract
         .client()
         .get_balance(wallet_address, None)
         .await
-        .map_err(|e| {
-            eprintln!("Balance check error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(ApiError::provider)?;
     eprintln!(
         "Wallet address: 0x{:x}, Balance: {} wei (~{} ETH)",
         wallet_address,
@@ -47,11 +111,7 @@ pub async fn register_asset(
     let gas_estimate = contract
         .register_asset(input.description.clone())
         .estimate_gas()
-        .await
-        .map_err(|e| {
-            eprintln!("Gas estimation error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .await?;
     let gas_limit = gas_estimate * 120 / 100; // 120% buffer
 
     eprintln!(
@@ -79,40 +139,59 @@ pub async fn register_asset(
     );
 
     if balance < required_funds {
-        eprintln!(
+        return Err(ApiError::bad_request(format!(
             "Insufficient funds: have {} wei, need {} wei",
             balance, required_funds
-        );
-        return Err(StatusCode::BAD_REQUEST);
+        )));
     }
 
+    let reservation = {
+        let conn = &mut state.db_pool.get()?;
+        state
+            .spending
+            .reserve(conn, wallet_address, &api_client, required_funds)?
+    };
+
     let call = contract
         .register_asset(input.description.clone())
         .gas(gas_limit)
         .gas_price(gas_price)
         .value(U256::zero());
-    let tx = call
-        .send()
-        .await
-        .map_err(|e| {
-            eprintln!("Transaction send error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .await
-        .map_err(|e| {
-            eprintln!("Transaction confirmation error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .ok_or_else(|| {
-            eprintln!("No transaction receipt");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let pending = match call.send().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            let conn = &mut state.db_pool.get()?;
+            reservation.cancel(conn)?;
+            return Err(e.into());
+        }
+    };
+    {
+        let conn = &mut state.db_pool.get()?;
+        reservation.broadcast(conn, *pending)?;
+        if let Some(item) = &job_item {
+            mark_submitted(conn, item.id, *pending)?;
+        }
+    }
+    let tx = pending
+        .await?
+        .ok_or_else(|| ApiError::internal("No transaction receipt"))?;
 
     // eprintln!("Transaction: {:?}", tx);
+    {
+        let conn = &mut state.db_pool.get()?;
+        settle(conn, &tx)?;
+    }
+    record_sent(contract.client().as_ref(), &state.db_pool, &tx).await;
 
     if tx.status != Some(1.into()) {
         eprintln!("Transaction failed: {:?}", tx);
-        return Err(StatusCode::BAD_REQUEST);
+        if let Some(item) = &job_item {
+            let conn = &mut state.db_pool.get()?;
+            mark_failed(conn, item, "Transaction reverted".to_string())?;
+        }
+        return Err(ApiError::ContractReverted(
+            "Transaction reverted".to_string(),
+        ));
     }
 
     let mut event_res = AssetRegisteredResponse::init();
@@ -126,37 +205,38 @@ pub async fn register_asset(
         if let Ok(event) = <AssetRegisteredFilter as EthEvent>::decode_log(&raw_log) {
             event_res = AssetRegisteredResponse::new(H256::from(event.asset_id), event.asset_owner);
 
-            let asset_id = format!("0x{}", hex::encode(event.asset_id));
-            let owner = format!("0x{}", hex::encode(event.asset_owner.0));
-            let asset = contract
-                .get_asset(event.asset_id)
-                .call()
-                .await
-                .map_err(|e| {
-                    eprintln!("getAsset call error: {:?}", e.to_string());
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
+            let asset_id = AssetId::from(event.asset_id);
+            let asset = contract.get_asset(event.asset_id).call().await?;
             let db_asset = DbAsset {
-                asset_id: asset_id.clone(),
-                owner,
+                asset_id,
+                owner: EvmAddress(event.asset_owner),
                 description: asset.description,
                 registered_at: asset.registered_at.as_u64() as i64,
             };
-
-            let conn = &mut state.db_pool.get().map_err(|e| {
-                eprintln!("DB connection error: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
+            let conn = &mut state.db_pool.get()?;
+            // The asset row commits together with its event and webhook outbox
+            conn.transaction::<_, ApiError, _>(|conn| {
+                diesel::insert_into(assets::table)
+                    .values(&db_asset)
+                    .on_conflict(assets::asset_id)
+                    .do_update()
+                    .set(&db_asset)
+                    .execute(conn)?;
+                record_event(
+                    conn,
+                    &NewAssetEvent::registered(
+                        asset_id,
+                        db_asset.owner,
+                        db_asset.registered_at,
+                        tx.transaction_hash,
+                        tx.block_number,
+                    ),
+                )?;
+                Ok(())
             })?;
-            diesel::insert_into(assets::table)
-                .values(&db_asset)
-                .on_conflict(assets::asset_id)
-                .do_update()
-                .set(&db_asset)
-                .execute(conn)
-                .map_err(|e| {
-                    eprintln!("Failed to insert asset: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
+            if let Some(item) = &job_item {
+                mark_confirmed(conn, item.id, &asset_id.to_string())?;
+            }
 
             println!("📦 Asset Registered:");
             println!("    Asset ID: {}", asset_id);
@@ -169,3 +249,29 @@ pub async fn register_asset(
         event_res.asset_id, event_res.asset_owner
     )))
 }
+
+// Settles the transaction an earlier attempt under the same idempotency key broadcast,
+// requeueing the job item if it never made it into a block
+async fn resume_registration(
+    state: &AppState,
+    contract: &SwitchContract,
+    item: &BatchJobItem,
+) -> Result<(), ApiError> {
+    let txn_hash = submitted_hash(item)?;
+    match await_receipt(contract.client().as_ref(), txn_hash).await? {
+        Some(receipt) => {
+            {
+                let conn = &mut state.db_pool.get()?;
+                settle(conn, &receipt)?;
+            }
+            record_sent(contract.client().as_ref(), &state.db_pool, &receipt).await;
+            finalize_registration(state, item, &receipt).await
+        }
+        None => {
+            eprintln!("Registration tx {:?} was dropped, resubmitting", txn_hash);
+            let conn = &mut state.db_pool.get()?;
+            release(conn, txn_hash)?;
+            mark_requeued(conn, item.id)
+        }
+    }
+}
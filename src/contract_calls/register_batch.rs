@@ -0,0 +1,281 @@
+use crate::address::EvmAddress;
+use crate::app_route::AssetRegisteredFilter;
+use crate::app_state::AppState;
+use crate::asset_id::AssetId;
+use crate::contract_calls::batch::{
+    MAX_BATCH_SIZE, NewBatchItem, STATUS_QUEUED, STATUS_SUBMITTED, Settlement, await_receipt,
+    check_submitted, job_response, load_items, load_or_create_job, mark_confirmed, mark_failed,
+    mark_submitted, requeue_dropped, submitted_hash,
+};
+use crate::custody::ActingContract;
+use crate::error::{ApiError, ErrorResponse};
+use crate::event_log::{NewAssetEvent, record_event};
+use crate::extract::ApiJson;
+use crate::idempotency::IdempotencyKey;
+use crate::models::{Asset as DbAsset, BatchJobItem, BatchJobResponse, RegisterBatchInput};
+use crate::schema::assets;
+use crate::spending::{ApiClient, settle};
+use crate::transactions::record_sent;
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use ethabi::RawLog;
+use ethers::prelude::*;
+use std::sync::Arc;
+
+pub const BATCH_KIND_REGISTER: &str = "register";
+
+#[utoipa::path(
+    post,
+    path = "/contract/register_batch",
+    request_body(content = RegisterBatchInput, content_type = "application/json"),
+    params(
+        ("x-switch-user" = Option<String>, Header, description = "Custodial user to act as, defaults to the service wallet"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Alternative to idempotency_key in the body, must match it when both are sent")
+    ),
+    responses(
+        (status = 200, description = "Batch processed, see per-item status", body = BatchJobResponse),
+        (status = 400, description = "Empty or oversized batch, or missing idempotency key", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role, or did not create the x-switch-user user", body = ErrorResponse),
+        (status = 409, description = "Idempotency key already used for a different batch", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn register_batch(
+    State(state): State<Arc<AppState>>,
+    ActingContract(contract): ActingContract,
+    ApiClient(api_client): ApiClient,
+    idempotency_key: IdempotencyKey,
+    ApiJson(input): ApiJson<RegisterBatchInput>,
+) -> Result<Json<BatchJobResponse>, ApiError> {
+    let idempotency_key = idempotency_key.or_body(&input.idempotency_key)?;
+    if input.descriptions.is_empty() || input.descriptions.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::bad_request(format!(
+            "Batch size {} outside of 1..={}",
+            input.descriptions.len(),
+            MAX_BATCH_SIZE
+        )));
+    }
+    let _in_flight = state.in_flight.claim(&api_client, &idempotency_key)?;
+
+    // Connections are checked out per database step and dropped before every send and receipt
+    // wait, so a large batch doesn't hold one for its whole run
+    let new_items = input
+        .descriptions
+        .iter()
+        .map(|description| NewBatchItem::register(description))
+        .collect::<Vec<_>>();
+    let (job, items) = {
+        let conn = &mut state.db_pool.get()?;
+        let job = load_or_create_job(
+            conn,
+            &api_client,
+            &idempotency_key,
+            BATCH_KIND_REGISTER,
+            contract.client().address(),
+            &new_items,
+        )?;
+        let items = load_items(conn, job.id)?;
+        (job, items)
+    };
+
+    let client = contract.client();
+
+    // Resuming: settle whatever an earlier run already broadcast before sending anything new
+    let mut in_flight = Vec::new();
+    for item in items
+        .into_iter()
+        .filter(|item| item.status == STATUS_SUBMITTED)
+    {
+        let txn_hash = submitted_hash(&item)?;
+        match check_submitted(client.as_ref(), txn_hash).await? {
+            Settlement::Mined(receipt) => settle_registration(&state, &item, &receipt).await?,
+            Settlement::Pending => in_flight.push((item, txn_hash)),
+            Settlement::Dropped => {
+                let conn = &mut state.db_pool.get()?;
+                requeue_dropped(conn, &item, txn_hash)?;
+            }
+        }
+    }
+
+    let wallet_address = client.address();
+    let mut balance = client
+        .get_balance(wallet_address, None)
+        .await
+        .map_err(ApiError::provider)?;
+    let gas_price = client
+        .get_gas_price()
+        .await
+        .unwrap_or(U256::from(2_000_000_000u64));
+    let mut nonce = client
+        .get_transaction_count(wallet_address, Some(BlockNumber::Pending.into()))
+        .await
+        .map_err(ApiError::provider)?;
+
+    // Queued items (never sent, or dropped and requeued) are submitted back to back with
+    // consecutive nonces, so the whole batch can land in the same few blocks. Items that already
+    // failed or were rejected keep that outcome: a replay reports it rather than trying again.
+    let to_send = {
+        let conn = &mut state.db_pool.get()?;
+        load_items(conn, job.id)?
+            .into_iter()
+            .filter(|item| item.status == STATUS_QUEUED)
+            .collect::<Vec<_>>()
+    };
+    for item in to_send {
+        let description = item.description.clone().unwrap_or_default();
+
+        let gas_estimate = match contract
+            .register_asset(description.clone())
+            .estimate_gas()
+            .await
+        {
+            Ok(gas_estimate) => gas_estimate,
+            Err(e) => {
+                let conn = &mut state.db_pool.get()?;
+                mark_failed(conn, &item, format!("Gas estimation failed: {}", e))?;
+                continue;
+            }
+        };
+        let gas_limit = gas_estimate * 120 / 100; // 120% buffer
+
+        let required_funds: U256 = gas_limit * gas_price;
+        let reservation = {
+            let conn = &mut state.db_pool.get()?;
+            if balance < required_funds {
+                mark_failed(
+                    conn,
+                    &item,
+                    format!(
+                        "Insufficient funds: have {} wei, need {} wei",
+                        balance, required_funds
+                    ),
+                )?;
+                continue;
+            }
+            match state
+                .spending
+                .reserve(conn, wallet_address, &api_client, required_funds)
+            {
+                Ok(reservation) => reservation,
+                Err(ApiError::TooManyRequests(reason)) => {
+                    mark_failed(conn, &item, reason)?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let call = contract
+            .register_asset(description)
+            .gas(gas_limit)
+            .gas_price(gas_price)
+            .nonce(nonce)
+            .value(U256::zero());
+        let sent = call.send().await;
+        let conn = &mut state.db_pool.get()?;
+        match sent {
+            Ok(pending) => {
+                let txn_hash = *pending;
+                mark_submitted(conn, item.id, txn_hash)?;
+                reservation.broadcast(conn, txn_hash)?;
+                in_flight.push((item, txn_hash));
+                nonce += U256::one();
+                balance -= required_funds;
+            }
+            Err(e) => {
+                reservation.cancel(conn)?;
+                mark_failed(conn, &item, format!("Transaction send failed: {}", e))?;
+            }
+        }
+    }
+
+    for (item, txn_hash) in in_flight {
+        match await_receipt(client.as_ref(), txn_hash).await? {
+            Some(receipt) => settle_registration(&state, &item, &receipt).await?,
+            None => {
+                let conn = &mut state.db_pool.get()?;
+                requeue_dropped(conn, &item, txn_hash)?;
+            }
+        }
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    Ok(Json(job_response(conn, &job)?))
+}
+
+// Books what a mined batch item actually cost, records its receipt and stores its asset
+async fn settle_registration(
+    state: &AppState,
+    item: &BatchJobItem,
+    receipt: &TransactionReceipt,
+) -> Result<(), ApiError> {
+    {
+        let conn = &mut state.db_pool.get()?;
+        settle(conn, receipt)?;
+    }
+    record_sent(state.contract.client().as_ref(), &state.db_pool, receipt).await;
+    finalize_registration(state, item, receipt).await
+}
+
+// Stores the asset a mined registerAsset produced and confirms the job item. The asset is
+// read back from the contract before a connection is taken for the writes.
+pub async fn finalize_registration(
+    state: &AppState,
+    item: &BatchJobItem,
+    receipt: &TransactionReceipt,
+) -> Result<(), ApiError> {
+    if receipt.status != Some(1.into()) {
+        let conn = &mut state.db_pool.get()?;
+        return mark_failed(conn, item, "Transaction reverted".to_string());
+    }
+
+    for log in receipt.logs.iter() {
+        let raw_log = RawLog {
+            topics: log.topics.clone(),
+            data: log.data.clone().to_vec(),
+        };
+
+        if let Ok(event) = <AssetRegisteredFilter as EthEvent>::decode_log(&raw_log) {
+            let asset_id = AssetId::from(event.asset_id);
+            let asset = state.contract.get_asset(event.asset_id).call().await?;
+            let db_asset = DbAsset {
+                asset_id,
+                owner: EvmAddress(event.asset_owner),
+                description: asset.description,
+                registered_at: asset.registered_at.as_u64() as i64,
+            };
+            let conn = &mut state.db_pool.get()?;
+            // The asset row commits together with its event and webhook outbox
+            conn.transaction::<_, ApiError, _>(|conn| {
+                diesel::insert_into(assets::table)
+                    .values(&db_asset)
+                    .on_conflict(assets::asset_id)
+                    .do_update()
+                    .set(&db_asset)
+                    .execute(conn)?;
+                record_event(
+                    conn,
+                    &NewAssetEvent::registered(
+                        asset_id,
+                        db_asset.owner,
+                        db_asset.registered_at,
+                        receipt.transaction_hash,
+                        receipt.block_number,
+                    ),
+                )?;
+                Ok(())
+            })?;
+
+            return mark_confirmed(conn, item.id, &asset_id.to_string());
+        }
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    mark_failed(
+        conn,
+        item,
+        "Receipt has no AssetRegistered event".to_string(),
+    )
+}
@@ -0,0 +1,191 @@
+use crate::address::EvmAddress;
+use crate::app_route::SwitchAssetsCalls;
+use crate::app_state::AppState;
+use crate::contract_calls::batch::{
+    NewBatchItem, STATUS_CONFIRMED, STATUS_SUBMITTED, await_receipt, job_response,
+    load_or_create_job, load_single_item, mark_failed, mark_submitted,
+};
+use crate::contract_calls::register_batch::finalize_registration;
+use crate::contract_calls::transfer_asset::validate_transfer;
+use crate::contract_calls::transfer_batch::finalize_transfer;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiJson;
+use crate::models::{RelayInput, RelayResponse};
+use crate::transactions::record_sent;
+use axum::{Json, extract::State};
+use ethers::core::abi::AbiDecode;
+use ethers::core::types::transaction::eip2718::TypedTransaction;
+use ethers::core::utils::rlp::Rlp;
+use ethers::prelude::*;
+use std::sync::Arc;
+
+pub const BATCH_KIND_RELAY: &str = "relay";
+// Relay jobs are keyed by transaction hash, which already names one signed transaction, so they
+// are shared by every client rather than scoped to the one that relayed it first
+const RELAY_CLIENT: &str = "relay";
+
+#[utoipa::path(
+    post,
+    path = "/contract/relay",
+    request_body(content = RelayInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Signed transaction broadcast and tracked", body = RelayResponse),
+        (status = 400, description = "Undecodable transaction, wrong target or function, or it would revert", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn relay_transaction(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<RelayInput>,
+) -> Result<Json<RelayResponse>, ApiError> {
+    let raw = hex::decode(
+        input
+            .raw_transaction
+            .strip_prefix("0x")
+            .unwrap_or(&input.raw_transaction),
+    )
+    .map_err(|e| ApiError::bad_request(format!("Invalid raw_transaction format: {:?}", e)))?;
+    let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw)).map_err(|e| {
+        ApiError::bad_request(format!("Failed to decode signed transaction: {:?}", e))
+    })?;
+
+    let contract = state.contract.clone();
+    let client = contract.client();
+
+    // Pre-EIP-155 transactions carry no chain id and could be replayed on any chain
+    let chain_id = client.signer().chain_id();
+    if tx.chain_id().map(|id| id.as_u64()) != Some(chain_id) {
+        return Err(ApiError::bad_request(format!(
+            "Relayed transaction chain id {:?} does not match {}",
+            tx.chain_id(),
+            chain_id
+        )));
+    }
+
+    let from = signature.recover(tx.sighash()).map_err(|e| {
+        ApiError::bad_request(format!("Failed to recover transaction sender: {:?}", e))
+    })?;
+    let txn_hash = tx.hash(&signature);
+
+    match tx.to() {
+        Some(NameOrAddress::Address(to)) if *to == contract.address() => {}
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "Relayed transaction targets {:?}, not SwitchAssets",
+                other
+            )));
+        }
+    }
+    if tx.value().is_some_and(|value| !value.is_zero()) {
+        return Err(ApiError::bad_request("Relayed transaction carries value"));
+    }
+
+    let data = tx.data().cloned().unwrap_or_default();
+    let new_item = match SwitchAssetsCalls::decode(data.as_ref()) {
+        Ok(SwitchAssetsCalls::RegisterAsset(call)) => NewBatchItem::register(&call.description),
+        Ok(SwitchAssetsCalls::TransferAsset(call)) => {
+            validate_transfer(&contract, from, H256::from(call.asset_id), call.new_owner).await?;
+            NewBatchItem::transfer(
+                &format!("0x{}", hex::encode(call.asset_id)),
+                &format!("0x{}", hex::encode(call.new_owner)),
+            )
+        }
+        Ok(_) => {
+            return Err(ApiError::bad_request(
+                "Relayed transaction calls a function other than registerAsset/transferAsset",
+            ));
+        }
+        Err(e) => {
+            return Err(ApiError::bad_request(format!(
+                "Failed to decode relayed calldata: {:?}",
+                e
+            )));
+        }
+    };
+
+    // Keyed by the transaction hash, so relaying the same bytes again resumes the same job.
+    // Connections are only held for the database steps, not while the node broadcasts or mines.
+    let idempotency_key = format!("relay:0x{}", hex::encode(txn_hash));
+    let _in_flight = state.in_flight.claim(RELAY_CLIENT, &idempotency_key)?;
+    let (job, item) = {
+        let conn = &mut state.db_pool.get()?;
+        let job = load_or_create_job(
+            conn,
+            RELAY_CLIENT,
+            &idempotency_key,
+            BATCH_KIND_RELAY,
+            from,
+            &[new_item],
+        )?;
+        let item = load_single_item(conn, job.id)?;
+        (job, item)
+    };
+
+    if item.status != STATUS_SUBMITTED && item.status != STATUS_CONFIRMED {
+        // Simulate as the sender before spending anyone's gas on a revert
+        let mut simulated = tx.clone();
+        simulated.set_from(from);
+        if let Err(e) = client.call(&simulated, None).await {
+            let conn = &mut state.db_pool.get()?;
+            mark_failed(conn, &item, format!("Simulation failed: {}", e))?;
+            return Err(ApiError::ContractReverted(format!(
+                "Relayed transaction would revert: {}",
+                e
+            )));
+        }
+
+        let broadcast = client
+            .send_raw_transaction(Bytes::from(raw))
+            .await
+            .map(|_| ());
+        let conn = &mut state.db_pool.get()?;
+        match broadcast {
+            Ok(()) => mark_submitted(conn, item.id, txn_hash)?,
+            Err(e) => {
+                mark_failed(conn, &item, format!("Broadcast failed: {}", e))?;
+                return Ok(Json(RelayResponse {
+                    txn_hash: format!("0x{}", hex::encode(txn_hash)),
+                    from: EvmAddress(from),
+                    job: job_response(conn, &job)?,
+                }));
+            }
+        }
+    }
+
+    if item.status != STATUS_CONFIRMED {
+        match await_receipt(client.as_ref(), txn_hash).await? {
+            Some(receipt) => {
+                record_sent(client.as_ref(), &state.db_pool, &receipt).await;
+                if item.description.is_some() {
+                    finalize_registration(&state, &item, &receipt).await?
+                } else {
+                    let conn = &mut state.db_pool.get()?;
+                    finalize_transfer(conn, &item, &receipt)?
+                }
+            }
+            None => {
+                // Only the client has the signed bytes, so the item can't be queued for this
+                // service to send again. Relaying the same transaction again resumes the job
+                // and rebroadcasts it.
+                eprintln!("Relayed tx {:?} was dropped", txn_hash);
+                let conn = &mut state.db_pool.get()?;
+                mark_failed(
+                    conn,
+                    &item,
+                    "Transaction was dropped before it was mined, relay it again to rebroadcast"
+                        .to_string(),
+                )?;
+            }
+        }
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    Ok(Json(RelayResponse {
+        txn_hash: format!("0x{}", hex::encode(txn_hash)),
+        from: EvmAddress(from),
+        job: job_response(conn, &job)?,
+    }))
+}
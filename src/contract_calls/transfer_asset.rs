@@ -1,11 +1,24 @@
-use crate::app_route::AssetRegisteredFilter;
-use crate::app_route::OwnershipTransferredFilter;
-use crate::app_state::AppState;
-use crate::models::{OwnershipTransferredResponse, TransferAssetInput};
+use crate::address::EvmAddress;
+use crate::app_route::{OwnershipTransferredFilter, SwitchAssets, SwitchAssetsErrors};
+use crate::app_state::{AppState, SwitchContract};
+use crate::asset_id::AssetId;
+use crate::contract_calls::batch::{
+    NewBatchItem, STATUS_CONFIRMED, STATUS_SUBMITTED, await_receipt, load_or_create_job,
+    load_single_item, mark_confirmed, mark_failed, mark_requeued, mark_submitted, replayed_failure,
+    submitted_hash,
+};
+use crate::contract_calls::transfer_batch::{BATCH_KIND_TRANSFER, finalize_transfer};
+use crate::custody::ActingContract;
+use crate::error::{ApiError, ErrorResponse};
+use crate::event_log::{NewAssetEvent, record_event};
+use crate::extract::ApiJson;
+use crate::idempotency::IdempotencyKey;
+use crate::models::{BatchJobItem, OwnershipTransferredResponse, TransferAssetInput};
 use crate::schema::{assets, transfers};
+use crate::spending::{ApiClient, release, settle};
+use crate::transactions::record_sent;
 use axum::Json;
 use axum::extract::State;
-use axum::http::StatusCode;
 use diesel::RunQueryDsl;
 use diesel::prelude::*;
 use ethabi::RawLog;
@@ -18,45 +31,82 @@ use std::sync::Arc;
     post,
     path = "/contract/transfer",
     request_body(content = TransferAssetInput, content_type = "application/json"),
+    params(
+        ("x-switch-user" = Option<String>, Header, description = "Custodial user to act as, defaults to the service wallet"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the original transfer instead of sending it again")
+    ),
     responses(
         (status = 200, description = "Asset transferred successfully", body = OwnershipTransferredResponse),
-        (status = 400, description = "Invalid asset ID, new owner address, or ownership issue"),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Invalid asset ID, new owner address, or ownership issue", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role, or did not create the x-switch-user user", body = ErrorResponse),
+        (status = 409, description = "Idempotency key already used for a different request, or still in progress", body = ErrorResponse),
+        (status = 429, description = "Wallet spending limit or client quota exceeded", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 pub async fn transfer_asset(
     State(state): State<Arc<AppState>>,
-    Json(input): Json<TransferAssetInput>,
-) -> eyre::Result<Json<OwnershipTransferredResponse>, StatusCode> {
-    let asset_id_bytes = hex::decode(input.asset_id.strip_prefix("0x").unwrap_or(&input.asset_id))
-        .map_err(|e| {
-            eprintln!("Invalid asset_id format: {:?}", e);
-            StatusCode::BAD_REQUEST
-        })?;
-    let asset_id = H256::from_slice(&asset_id_bytes);
-    let new_owner_bytes = hex::decode(
-        input
-            .new_owner
-            .strip_prefix("0x")
-            .unwrap_or(&input.new_owner),
-    )
-    .map_err(|e| {
-        eprintln!("Invalid new_owner format: {:?}", e);
-        StatusCode::BAD_REQUEST
-    })?;
-    let new_owner = H160::from_slice(&new_owner_bytes);
-
-    let contract = state.contract.clone();
+    ActingContract(contract): ActingContract,
+    ApiClient(api_client): ApiClient,
+    IdempotencyKey(idempotency_key): IdempotencyKey,
+    ApiJson(input): ApiJson<TransferAssetInput>,
+) -> eyre::Result<Json<OwnershipTransferredResponse>, ApiError> {
+    let (asset_id, new_owner) = (input.asset_id.0, input.new_owner.0);
+
     let wallet_address = contract.client().address();
+
+    // Connections are only checked out for each database step and dropped before anything
+    // waits on the chain, which can take many seconds, so slow writes can't drain the pool.
+
+    // A keyed transfer is tracked as a one-item job. Replays must be answered before
+    // validation, which would otherwise fail once the first attempt has moved the asset.
+    let _in_flight = idempotency_key
+        .as_deref()
+        .map(|key| state.in_flight.claim(&api_client, key))
+        .transpose()?;
+    let mut job_item = None;
+    if let Some(key) = &idempotency_key {
+        let (job, mut item) = {
+            let conn = &mut state.db_pool.get()?;
+            let job = load_or_create_job(
+                conn,
+                &api_client,
+                key,
+                BATCH_KIND_TRANSFER,
+                wallet_address,
+                &[NewBatchItem::transfer(
+                    &format!("0x{}", hex::encode(asset_id)),
+                    &format!("0x{}", hex::encode(new_owner)),
+                )],
+            )?;
+            let item = load_single_item(conn, job.id)?;
+            (job, item)
+        };
+        if item.status == STATUS_SUBMITTED {
+            resume_transfer(&state, &contract, &item).await?;
+            let conn = &mut state.db_pool.get()?;
+            item = load_single_item(conn, job.id)?;
+        }
+        if item.status == STATUS_CONFIRMED {
+            return Ok(Json(OwnershipTransferredResponse::new(
+                asset_id,
+                wallet_address,
+                new_owner,
+            )));
+        }
+        if let Some(error) = replayed_failure(&item) {
+            return Err(error);
+        }
+        job_item = Some(item);
+    }
+
     let balance = contract
         .client()
         .get_balance(wallet_address, None)
         .await
-        .map_err(|e| {
-            eprintln!("Balance check error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(ApiError::provider)?;
     eprintln!(
         "Wallet address: 0x{:x}, Balance: {} wei (~{} ETH)",
         wallet_address,
@@ -64,34 +114,18 @@ pub async fn transfer_asset(
         balance.as_u128() as f64 / 1e18
     );
 
-    let asset = contract
-        .get_asset(asset_id.into())
-        .call()
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to get asset {}: {:?}", hex::encode(asset_id), e);
-            StatusCode::BAD_REQUEST
-        })?;
-    if asset.asset_owner != wallet_address {
-        eprintln!(
-            "Wallet 0x{:x} is not the owner of asset {}",
-            wallet_address,
-            hex::encode(asset_id)
-        );
-        return Err(StatusCode::BAD_REQUEST);
-    }
+    validate_transfer(&contract, wallet_address, asset_id, new_owner).await?;
 
     let gas_estimate = contract
         .transfer_asset(<[u8; 32]>::from(asset_id), new_owner)
         .estimate_gas()
         .await
         .map_err(|e| {
-            eprintln!(
+            ApiError::bad_request(format!(
                 "Gas estimation error for asset_id {}: {:?}",
                 hex::encode(asset_id),
                 e
-            );
-            StatusCode::BAD_REQUEST
+            ))
         })?;
     let gas_limit = gas_estimate * 120 / 100;
 
@@ -120,39 +154,59 @@ pub async fn transfer_asset(
     );
 
     if balance < required_funds {
-        eprintln!(
+        return Err(ApiError::bad_request(format!(
             "Insufficient funds: have {} wei, need {} wei",
             balance, required_funds
-        );
-        return Err(StatusCode::BAD_REQUEST);
+        )));
     }
 
+    let reservation = {
+        let conn = &mut state.db_pool.get()?;
+        state
+            .spending
+            .reserve(conn, wallet_address, &api_client, required_funds)?
+    };
+
     let call = contract
         .transfer_asset(<[u8; 32]>::from(asset_id), new_owner)
         .gas(gas_limit)
         .gas_price(gas_price)
         .value(U256::zero());
 
-    let tx = call
-        .send()
-        .await
-        .map_err(|e| {
-            eprintln!("Transaction send error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .await
-        .map_err(|e| {
-            eprintln!("Transaction confirmation error: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .ok_or_else(|| {
-            eprintln!("No transaction receipt");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let pending = match call.send().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            let conn = &mut state.db_pool.get()?;
+            reservation.cancel(conn)?;
+            return Err(e.into());
+        }
+    };
+    {
+        let conn = &mut state.db_pool.get()?;
+        reservation.broadcast(conn, *pending)?;
+        if let Some(item) = &job_item {
+            mark_submitted(conn, item.id, *pending)?;
+        }
+    }
+    let tx = pending
+        .await?
+        .ok_or_else(|| ApiError::internal("No transaction receipt"))?;
+
+    {
+        let conn = &mut state.db_pool.get()?;
+        settle(conn, &tx)?;
+    }
+    record_sent(contract.client().as_ref(), &state.db_pool, &tx).await;
 
+    let conn = &mut state.db_pool.get()?;
     if tx.status != Some(1.into()) {
         eprintln!("Transaction failed: {:?}", tx);
-        return Err(StatusCode::BAD_REQUEST);
+        if let Some(item) = &job_item {
+            mark_failed(conn, item, "Transaction reverted".to_string())?;
+        }
+        return Err(ApiError::ContractReverted(
+            "Transaction reverted".to_string(),
+        ));
     }
 
     let mut event_res = OwnershipTransferredResponse::init();
@@ -168,64 +222,164 @@ pub async fn transfer_asset(
                 event.new_owner,
             );
 
-            let db_asset_id = format!("0x{}", hex::encode(event.asset_id));
-            let db_old_owner = format!("0x{}", hex::encode(event.old_owner.0));
-            let db_new_owner = format!("0x{}", hex::encode(event.new_owner.0));
-            let transaction_hash = format!("0x{}", hex::encode(tx.transaction_hash));
-            let timestamp = chrono::Utc::now().timestamp();
-
-            let conn = &mut state.db_pool.get().map_err(|e| {
-                eprintln!("DB connection error: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-
-            // Check if transfer exists
-            let exists: bool = transfers::table
-                .filter(transfers::asset_id.eq(&db_asset_id))
-                .filter(transfers::txn_hash.eq(&transaction_hash))
-                .select(diesel::dsl::count_star())
-                .first::<i64>(conn)
-                .unwrap()
-                > 0;
-
-            if exists {
-                eprintln!(
-                    "Skipping duplicate transfer for asset {} (tx: {})",
-                    db_asset_id, transaction_hash
-                );
-                continue;
+            store_transfer_event(conn, &event, &tx)?;
+            if let Some(item) = &job_item {
+                mark_confirmed(conn, item.id, &event_res.asset_id.to_string())?;
             }
 
-            diesel::insert_into(transfers::table)
-                .values((
-                    transfers::asset_id.eq(&db_asset_id),
-                    transfers::old_owner.eq(&db_old_owner),
-                    transfers::new_owner.eq(&db_new_owner),
-                    transfers::timestamp.eq(timestamp),
-                    transfers::txn_hash.eq(&transaction_hash),
-                ))
-                .execute(conn)
-                .map_err(|e| {
-                    eprintln!("Failed to insert transfer: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
-
-            diesel::update(assets::table)
-                .filter(assets::asset_id.eq(&db_asset_id))
-                .set(assets::owner.eq(&db_new_owner))
-                .execute(conn)
-                .map_err(|e| {
-                    eprintln!("Failed to update asset owner: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
-
             println!("📦 Ownership Transferred:");
-            println!("    Asset ID: {}", db_asset_id);
-            println!("    Old Owner: {}", db_old_owner);
-            println!("    New Owner: {}", db_new_owner);
-            println!("    Tx Hash: {}", transaction_hash);
+            println!("    Asset ID: {}", event_res.asset_id);
+            println!("    Old Owner: {}", event_res.old_owner);
+            println!("    New Owner: {}", event_res.new_owner);
+            println!("    Tx Hash: 0x{}", hex::encode(tx.transaction_hash));
         }
     }
 
     Ok(Json(event_res))
 }
+
+// Settles the transaction an earlier attempt under the same idempotency key broadcast,
+// requeueing the job item if it never made it into a block
+async fn resume_transfer(
+    state: &AppState,
+    contract: &SwitchContract,
+    item: &BatchJobItem,
+) -> Result<(), ApiError> {
+    let txn_hash = submitted_hash(item)?;
+    match await_receipt(contract.client().as_ref(), txn_hash).await? {
+        Some(receipt) => {
+            {
+                let conn = &mut state.db_pool.get()?;
+                settle(conn, &receipt)?;
+            }
+            record_sent(contract.client().as_ref(), &state.db_pool, &receipt).await;
+            let conn = &mut state.db_pool.get()?;
+            finalize_transfer(conn, item, &receipt)
+        }
+        None => {
+            eprintln!("Transfer tx {:?} was dropped, resubmitting", txn_hash);
+            let conn = &mut state.db_pool.get()?;
+            release(conn, txn_hash)?;
+            mark_requeued(conn, item.id)
+        }
+    }
+}
+
+// Decodes the stored hex of a queued transfer, refusing anything that is not exactly 32/20 bytes
+pub fn parse_transfer_input(asset_id: &str, new_owner: &str) -> Result<(H256, H160), String> {
+    Ok((
+        AssetId::parse("asset_id", asset_id)?.0,
+        EvmAddress::parse("new_owner", new_owner)?.0,
+    ))
+}
+
+// Mirrors the revert conditions of SwitchAssets.transferAsset against current on-chain state,
+// so callers can refuse a transfer before paying gas for it
+pub async fn validate_transfer<M: Middleware>(
+    contract: &SwitchAssets<M>,
+    caller: H160,
+    asset_id: H256,
+    new_owner: H160,
+) -> Result<(), ApiError> {
+    let asset = contract
+        .get_asset(asset_id.into())
+        .call()
+        .await
+        .map_err(|e| {
+            // A custom error revert carries its selector, not its name, in the error text
+            if let Some(SwitchAssetsErrors::ASSET_DOES_NOT_EXIST(_)) =
+                e.decode_contract_revert::<SwitchAssetsErrors>()
+            {
+                ApiError::bad_request(format!(
+                    "ASSET_DOES_NOT_EXIST: asset 0x{}",
+                    hex::encode(asset_id)
+                ))
+            } else {
+                ApiError::Contract(format!(
+                    "Failed to get asset 0x{}: {}",
+                    hex::encode(asset_id),
+                    e
+                ))
+            }
+        })?;
+
+    if asset.asset_owner != caller {
+        return Err(ApiError::bad_request(format!(
+            "ONLY_OWNER: 0x{:x} is not the owner of asset 0x{}",
+            caller,
+            hex::encode(asset_id)
+        )));
+    }
+    if new_owner == caller {
+        return Err(ApiError::bad_request(
+            "INVALID_TRANSACTION: cannot transfer an asset to its current owner".to_string(),
+        ));
+    }
+    if new_owner == H160::zero() {
+        return Err(ApiError::bad_request(
+            "ADDRESS_ZERO: new owner cannot be the zero address".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Records an OwnershipTransferred event from one of our own receipts, skipping duplicates
+pub fn store_transfer_event(
+    conn: &mut PgConnection,
+    event: &OwnershipTransferredFilter,
+    receipt: &TransactionReceipt,
+) -> Result<(), ApiError> {
+    let db_asset_id = format!("0x{}", hex::encode(event.asset_id));
+    let db_old_owner = EvmAddress(event.old_owner).to_string();
+    let db_new_owner = EvmAddress(event.new_owner).to_string();
+    let transaction_hash = format!("0x{}", hex::encode(receipt.transaction_hash));
+    let timestamp = chrono::Utc::now().timestamp();
+
+    // Check if transfer exists
+    let exists: bool = transfers::table
+        .filter(transfers::asset_id.eq(&db_asset_id))
+        .filter(transfers::txn_hash.eq(&transaction_hash))
+        .select(diesel::dsl::count_star())
+        .first::<i64>(conn)?
+        > 0;
+
+    if exists {
+        eprintln!(
+            "Skipping duplicate transfer for asset {} (tx: {})",
+            db_asset_id, transaction_hash
+        );
+        return Ok(());
+    }
+
+    // The transfer and owner change commit together with the event and its webhook outbox
+    conn.transaction(|conn| {
+        diesel::insert_into(transfers::table)
+            .values((
+                transfers::asset_id.eq(&db_asset_id),
+                transfers::old_owner.eq(&db_old_owner),
+                transfers::new_owner.eq(&db_new_owner),
+                transfers::timestamp.eq(timestamp),
+                transfers::txn_hash.eq(&transaction_hash),
+            ))
+            .execute(conn)?;
+        record_event(
+            conn,
+            &NewAssetEvent::transferred(
+                AssetId::from(event.asset_id),
+                EvmAddress(event.old_owner),
+                EvmAddress(event.new_owner),
+                timestamp,
+                receipt.transaction_hash,
+                receipt.block_number,
+            ),
+        )?;
+
+        diesel::update(assets::table)
+            .filter(assets::asset_id.eq(&db_asset_id))
+            .set(assets::owner.eq(&db_new_owner))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
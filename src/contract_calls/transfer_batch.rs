@@ -0,0 +1,303 @@
+use crate::app_route::OwnershipTransferredFilter;
+use crate::app_state::AppState;
+use crate::contract_calls::batch::{
+    MAX_BATCH_SIZE, NewBatchItem, STATUS_CONFIRMED, STATUS_QUEUED, STATUS_SUBMITTED, Settlement,
+    await_receipt, check_submitted, job_response, load_items, load_or_create_job, mark_confirmed,
+    mark_failed, mark_rejected, mark_submitted, requeue_dropped, submitted_hash,
+};
+use crate::contract_calls::transfer_asset::{
+    parse_transfer_input, store_transfer_event, validate_transfer,
+};
+use crate::custody::ActingContract;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiJson;
+use crate::idempotency::IdempotencyKey;
+use crate::models::{BatchJobItem, BatchJobResponse, TransferBatchInput};
+use crate::spending::{ApiClient, settle};
+use crate::transactions::record_sent;
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use ethabi::RawLog;
+use ethers::prelude::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub const BATCH_KIND_TRANSFER: &str = "transfer";
+
+#[utoipa::path(
+    post,
+    path = "/contract/transfer_batch",
+    request_body(content = TransferBatchInput, content_type = "application/json"),
+    params(
+        ("x-switch-user" = Option<String>, Header, description = "Custodial user to act as, defaults to the service wallet"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Alternative to idempotency_key in the body, must match it when both are sent")
+    ),
+    responses(
+        (status = 200, description = "Batch processed, see per-item status", body = BatchJobResponse),
+        (status = 400, description = "Empty or oversized batch, or missing idempotency key", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role, or did not create the x-switch-user user", body = ErrorResponse),
+        (status = 409, description = "Idempotency key already used for a different batch", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn transfer_batch(
+    State(state): State<Arc<AppState>>,
+    ActingContract(contract): ActingContract,
+    ApiClient(api_client): ApiClient,
+    idempotency_key: IdempotencyKey,
+    ApiJson(input): ApiJson<TransferBatchInput>,
+) -> Result<Json<BatchJobResponse>, ApiError> {
+    let idempotency_key = idempotency_key.or_body(&input.idempotency_key)?;
+    if input.transfers.is_empty() || input.transfers.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::bad_request(format!(
+            "Batch size {} outside of 1..={}",
+            input.transfers.len(),
+            MAX_BATCH_SIZE
+        )));
+    }
+    let _in_flight = state.in_flight.claim(&api_client, &idempotency_key)?;
+
+    // Connections are checked out per database step and dropped before every send and receipt
+    // wait, so a large batch doesn't hold one for its whole run
+    let new_items = input
+        .transfers
+        .iter()
+        .map(|transfer| {
+            NewBatchItem::transfer(
+                &transfer.asset_id.to_string(),
+                &transfer.new_owner.to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (job, items) = {
+        let conn = &mut state.db_pool.get()?;
+        let job = load_or_create_job(
+            conn,
+            &api_client,
+            &idempotency_key,
+            BATCH_KIND_TRANSFER,
+            contract.client().address(),
+            &new_items,
+        )?;
+        let items = load_items(conn, job.id)?;
+        (job, items)
+    };
+
+    let client = contract.client();
+
+    // Resuming: settle whatever an earlier run already broadcast before sending anything new
+    let mut in_flight = Vec::new();
+    for item in items
+        .into_iter()
+        .filter(|item| item.status == STATUS_SUBMITTED)
+    {
+        let txn_hash = submitted_hash(&item)?;
+        match check_submitted(client.as_ref(), txn_hash).await? {
+            Settlement::Mined(receipt) => settle_transfer(&state, &item, &receipt).await?,
+            Settlement::Pending => in_flight.push((item, txn_hash)),
+            Settlement::Dropped => {
+                let conn = &mut state.db_pool.get()?;
+                requeue_dropped(conn, &item, txn_hash)?;
+            }
+        }
+    }
+
+    let wallet_address = client.address();
+    let mut balance = client
+        .get_balance(wallet_address, None)
+        .await
+        .map_err(ApiError::provider)?;
+    let gas_price = client
+        .get_gas_price()
+        .await
+        .unwrap_or(U256::from(2_000_000_000u64));
+    let mut nonce = client
+        .get_transaction_count(wallet_address, Some(BlockNumber::Pending.into()))
+        .await
+        .map_err(ApiError::provider)?;
+
+    let items = {
+        let conn = &mut state.db_pool.get()?;
+        load_items(conn, job.id)?
+    };
+
+    // Assets already moved (or about to be) by this batch can't be moved again by a later slot
+    let mut claimed_assets = items
+        .iter()
+        .filter(|item| item.status == STATUS_SUBMITTED || item.status == STATUS_CONFIRMED)
+        .filter_map(|item| {
+            parse_transfer_input(
+                item.asset_id.as_deref().unwrap_or_default(),
+                item.new_owner.as_deref().unwrap_or_default(),
+            )
+            .ok()
+        })
+        .map(|(asset_id, _)| asset_id)
+        .collect::<HashSet<_>>();
+
+    // Only queued items are sent; ones that already failed or were rejected keep that outcome,
+    // so a replay reports it rather than trying again
+    let to_send = items
+        .into_iter()
+        .filter(|item| item.status == STATUS_QUEUED);
+    for item in to_send {
+        let raw_asset_id = item.asset_id.clone().unwrap_or_default();
+        let raw_new_owner = item.new_owner.clone().unwrap_or_default();
+
+        let (asset_id, new_owner) = match parse_transfer_input(&raw_asset_id, &raw_new_owner) {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                let conn = &mut state.db_pool.get()?;
+                mark_rejected(conn, &item, reason)?;
+                continue;
+            }
+        };
+        if !claimed_assets.insert(asset_id) {
+            let conn = &mut state.db_pool.get()?;
+            mark_rejected(
+                conn,
+                &item,
+                "Asset appears more than once in this batch".to_string(),
+            )?;
+            continue;
+        }
+
+        // Only the checks that transferAsset itself would revert on reject the item outright
+        match validate_transfer(&contract, wallet_address, asset_id, new_owner).await {
+            Ok(()) => {}
+            Err(ApiError::BadRequest(reason)) => {
+                let conn = &mut state.db_pool.get()?;
+                mark_rejected(conn, &item, reason)?;
+                continue;
+            }
+            Err(e) => {
+                let conn = &mut state.db_pool.get()?;
+                mark_failed(conn, &item, e.to_string())?;
+                continue;
+            }
+        }
+
+        let gas_estimate = match contract
+            .transfer_asset(<[u8; 32]>::from(asset_id), new_owner)
+            .estimate_gas()
+            .await
+        {
+            Ok(gas_estimate) => gas_estimate,
+            Err(e) => {
+                let conn = &mut state.db_pool.get()?;
+                mark_failed(conn, &item, format!("Gas estimation failed: {}", e))?;
+                continue;
+            }
+        };
+        let gas_limit = gas_estimate * 120 / 100;
+
+        let required_funds: U256 = gas_limit * gas_price;
+        let reservation = {
+            let conn = &mut state.db_pool.get()?;
+            if balance < required_funds {
+                mark_failed(
+                    conn,
+                    &item,
+                    format!(
+                        "Insufficient funds: have {} wei, need {} wei",
+                        balance, required_funds
+                    ),
+                )?;
+                continue;
+            }
+            match state
+                .spending
+                .reserve(conn, wallet_address, &api_client, required_funds)
+            {
+                Ok(reservation) => reservation,
+                Err(ApiError::TooManyRequests(reason)) => {
+                    mark_failed(conn, &item, reason)?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let call = contract
+            .transfer_asset(<[u8; 32]>::from(asset_id), new_owner)
+            .gas(gas_limit)
+            .gas_price(gas_price)
+            .nonce(nonce)
+            .value(U256::zero());
+        let sent = call.send().await;
+        let conn = &mut state.db_pool.get()?;
+        match sent {
+            Ok(pending) => {
+                let txn_hash = *pending;
+                mark_submitted(conn, item.id, txn_hash)?;
+                reservation.broadcast(conn, txn_hash)?;
+                in_flight.push((item, txn_hash));
+                nonce += U256::one();
+                balance -= required_funds;
+            }
+            Err(e) => {
+                reservation.cancel(conn)?;
+                mark_failed(conn, &item, format!("Transaction send failed: {}", e))?;
+            }
+        }
+    }
+
+    for (item, txn_hash) in in_flight {
+        match await_receipt(client.as_ref(), txn_hash).await? {
+            Some(receipt) => settle_transfer(&state, &item, &receipt).await?,
+            None => {
+                let conn = &mut state.db_pool.get()?;
+                requeue_dropped(conn, &item, txn_hash)?;
+            }
+        }
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    Ok(Json(job_response(conn, &job)?))
+}
+
+// Books what a mined batch item actually cost, records its receipt and stores its transfer
+async fn settle_transfer(
+    state: &AppState,
+    item: &BatchJobItem,
+    receipt: &TransactionReceipt,
+) -> Result<(), ApiError> {
+    {
+        let conn = &mut state.db_pool.get()?;
+        settle(conn, receipt)?;
+    }
+    record_sent(state.contract.client().as_ref(), &state.db_pool, receipt).await;
+    let conn = &mut state.db_pool.get()?;
+    finalize_transfer(conn, item, receipt)
+}
+
+// Stores the transfer a mined transferAsset produced and confirms the job item
+pub fn finalize_transfer(
+    conn: &mut PgConnection,
+    item: &BatchJobItem,
+    receipt: &TransactionReceipt,
+) -> Result<(), ApiError> {
+    if receipt.status != Some(1.into()) {
+        return mark_failed(conn, item, "Transaction reverted".to_string());
+    }
+
+    for log in receipt.logs.iter() {
+        let raw_log = RawLog {
+            topics: log.topics.clone(),
+            data: log.data.clone().to_vec(),
+        };
+
+        if let Ok(event) = <OwnershipTransferredFilter as EthEvent>::decode_log(&raw_log) {
+            store_transfer_event(conn, &event, receipt)?;
+            return mark_confirmed(conn, item.id, item.asset_id.as_deref().unwrap_or_default());
+        }
+    }
+
+    mark_failed(
+        conn,
+        item,
+        "Receipt has no OwnershipTransferred event".to_string(),
+    )
+}
@@ -0,0 +1,208 @@
+use crate::app_state::{AppState, SwitchContract};
+use crate::auth::{API_KEY_HEADER, ApiKeyIdentity};
+use crate::error::ApiError;
+use crate::models::CustodyUser;
+use crate::schema::custody_users;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use diesel::prelude::*;
+use ethers::prelude::*;
+use eyre::Result;
+use std::env;
+use std::sync::Arc;
+
+// Names the application user whose custodial key should sign a write
+pub const ACTING_USER_HEADER: &str = "x-switch-user";
+
+const NONCE_LEN: usize = 12;
+
+// Seals per-user signing keys with AES-256-GCM under CUSTODY_MASTER_KEY. The owning address is
+// bound in as associated data, so a key blob copied onto another user's row will not open.
+#[derive(Clone)]
+pub struct Custody {
+    cipher: Aes256Gcm,
+}
+
+impl Custody {
+    pub fn from_env() -> Result<Option<Custody>> {
+        let Ok(master_key) = env::var("CUSTODY_MASTER_KEY") else {
+            println!("CUSTODY_MASTER_KEY not set, custodial users are disabled");
+            return Ok(None);
+        };
+        let key_bytes = hex::decode(master_key.strip_prefix("0x").unwrap_or(&master_key))
+            .map_err(|e| eyre::eyre!("Invalid CUSTODY_MASTER_KEY: {}", e))?;
+        if key_bytes.len() != 32 {
+            return Err(eyre::eyre!(
+                "CUSTODY_MASTER_KEY must be 32 bytes, got {}",
+                key_bytes.len()
+            ));
+        }
+
+        Ok(Some(Custody {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes)),
+        }))
+    }
+
+    // Creates a fresh signing key, returning its address and the sealed key to store
+    pub fn generate(&self) -> Result<(Address, String)> {
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let sealed = self.seal(wallet.address(), &wallet.signer().to_bytes())?;
+        Ok((wallet.address(), sealed))
+    }
+
+    pub fn open(&self, user: &CustodyUser) -> Result<LocalWallet> {
+        let address: Address = user.address.0;
+        let sealed = hex::decode(
+            user.encrypted_key
+                .strip_prefix("0x")
+                .unwrap_or(&user.encrypted_key),
+        )?;
+        if sealed.len() <= NONCE_LEN {
+            return Err(eyre::eyre!("Sealed key for {} is truncated", user.username));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let secret = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: address.as_bytes(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("Failed to unseal signing key for {}", user.username))?;
+
+        let wallet = LocalWallet::from_bytes(&secret)?;
+        if wallet.address() != address {
+            return Err(eyre::eyre!(
+                "Unsealed key for {} does not match its address",
+                user.username
+            ));
+        }
+        Ok(wallet)
+    }
+
+    fn seal(&self, address: Address, secret: &[u8]) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: secret,
+                    aad: address.as_bytes(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("Failed to seal signing key"))?;
+        Ok(format!(
+            "0x{}{}",
+            hex::encode(nonce),
+            hex::encode(ciphertext)
+        ))
+    }
+}
+
+// The contract handle a request acts through: the custodial wallet of the user named in
+// the x-switch-user header, or the service wallet when the header is absent. Naming a user
+// takes the API key that created the user, so a caller can only sign with its own users' keys.
+pub struct ActingContract(pub SwitchContract);
+
+impl FromRequestParts<Arc<AppState>> for ActingContract {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(header) = parts.headers.get(ACTING_USER_HEADER) else {
+            return Ok(ActingContract(state.contract.clone()));
+        };
+        let username = header
+            .to_str()
+            .map_err(|_| ApiError::bad_request(format!("Invalid {} header", ACTING_USER_HEADER)))?;
+        let Some(api_key) = parts.extensions.get::<ApiKeyIdentity>() else {
+            return Err(ApiError::unauthorized(format!(
+                "Acting as a custodial user needs an API key in the {} header",
+                API_KEY_HEADER
+            )));
+        };
+
+        let custody = state.custody.as_ref().ok_or_else(|| {
+            ApiError::unavailable("Acting user requested but custody is not configured")
+        })?;
+        let conn = &mut state.db_pool.get()?;
+        let user = custody_users::table
+            .filter(custody_users::username.eq(username))
+            .select(CustodyUser::as_select())
+            .first::<CustodyUser>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::not_found(format!("Unknown custody user: {}", username)))?;
+        if user.api_key_id != Some(api_key.id) {
+            return Err(ApiError::forbidden(format!(
+                "API key {} did not create custody user {} and cannot act as it",
+                api_key.name, username
+            )));
+        }
+
+        let wallet = custody.open(&user)?;
+        Ok(ActingContract(state.contract_for(wallet)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::EvmAddress;
+
+    fn custody(master_key: [u8; 32]) -> Custody {
+        Custody {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&master_key)),
+        }
+    }
+
+    fn user(username: &str, address: Address, encrypted_key: String) -> CustodyUser {
+        CustodyUser {
+            username: username.to_string(),
+            address: EvmAddress(address),
+            encrypted_key,
+            created_at: 0,
+            api_key_id: Some(1),
+        }
+    }
+
+    #[test]
+    fn sealed_key_opens_to_the_same_wallet() {
+        let custody = custody([7; 32]);
+        let (address, sealed) = custody.generate().unwrap();
+        let wallet = custody.open(&user("alice", address, sealed)).unwrap();
+        assert_eq!(wallet.address(), address);
+    }
+
+    #[test]
+    fn sealed_key_copied_to_another_user_does_not_open() {
+        let custody = custody([7; 32]);
+        let (_, alice_sealed) = custody.generate().unwrap();
+        let (bob_address, _) = custody.generate().unwrap();
+        let error = custody
+            .open(&user("bob", bob_address, alice_sealed))
+            .unwrap_err();
+        assert!(
+            error.to_string().starts_with("Failed to unseal"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn sealed_key_does_not_open_under_another_master_key() {
+        let (address, sealed) = custody([7; 32]).generate().unwrap();
+        assert!(
+            custody([8; 32])
+                .open(&user("alice", address, sealed))
+                .is_err()
+        );
+    }
+}
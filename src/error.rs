@@ -0,0 +1,305 @@
+use crate::address::EvmAddress;
+use crate::app_route::SwitchAssetsErrors;
+use axum::Json;
+use axum::extract::Request;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use ethers::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Echoed from the request when the client sends one, otherwise generated, and returned on
+// every response so a client report can be matched to the server log
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// Body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    // Stable machine-readable error kind, e.g. "not_found" or "contract_reverted"
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    pub request_id: String,
+}
+
+// Every way a handler can fail. Client errors carry a message meant for the caller; the
+// others carry their source, which is logged but not returned.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    TooManyRequests(String),
+    Unavailable(String),
+    Validation(validator::ValidationErrors),
+    InvalidHex(hex::FromHexError),
+    ContractReverted(String),
+    Contract(String),
+    Provider(String),
+    Database(diesel::result::Error),
+    Pool(diesel::r2d2::PoolError),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::BadRequest(message.into())
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        ApiError::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        ApiError::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::NotFound(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ApiError::Conflict(message.into())
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        ApiError::Unavailable(message.into())
+    }
+
+    // Errors from a Middleware stack, which are generic over the stack's layers
+    pub fn provider(e: impl std::fmt::Display) -> Self {
+        ApiError::Provider(e.to_string())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::Internal(message.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::Validation(_) | ApiError::InvalidHex(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) | ApiError::Pool(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::ContractReverted(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Contract(_) | ApiError::Provider(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Database(diesel::result::Error::NotFound) => StatusCode::NOT_FOUND,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) | ApiError::Database(diesel::result::Error::NotFound) => {
+                "not_found"
+            }
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::Unavailable(_) => "unavailable",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::InvalidHex(_) => "invalid_hex",
+            ApiError::ContractReverted(_) => "contract_reverted",
+            ApiError::Contract(_) => "contract_error",
+            ApiError::Provider(_) => "provider_error",
+            ApiError::Database(_) => "database_error",
+            ApiError::Pool(_) => "database_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn message_and_details(&self) -> (String, Option<String>) {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::TooManyRequests(message)
+            | ApiError::Unavailable(message) => (message.clone(), None),
+            ApiError::Validation(errors) => (
+                "Request validation failed".to_string(),
+                Some(errors.to_string()),
+            ),
+            ApiError::InvalidHex(e) => ("Invalid hex value".to_string(), Some(e.to_string())),
+            ApiError::ContractReverted(reason) => {
+                ("Contract call reverted".to_string(), Some(reason.clone()))
+            }
+            ApiError::Contract(_) => ("Contract call failed".to_string(), None),
+            ApiError::Provider(_) => ("Blockchain node request failed".to_string(), None),
+            ApiError::Database(diesel::result::Error::NotFound) => ("Not found".to_string(), None),
+            ApiError::Database(_) => ("Database error".to_string(), None),
+            ApiError::Pool(_) => ("Database unavailable".to_string(), None),
+            ApiError::Internal(_) => ("Internal server error".to_string(), None),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::TooManyRequests(message)
+            | ApiError::Unavailable(message)
+            | ApiError::ContractReverted(message)
+            | ApiError::Contract(message)
+            | ApiError::Provider(message)
+            | ApiError::Internal(message) => f.write_str(message),
+            ApiError::Validation(e) => write!(f, "{}", e),
+            ApiError::InvalidHex(e) => write!(f, "{}", e),
+            ApiError::Database(e) => write!(f, "{}", e),
+            ApiError::Pool(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = REQUEST_ID
+            .try_with(String::clone)
+            .unwrap_or_else(|_| new_request_id());
+        let status = self.status();
+        eprintln!("[{}] {} {:?}", request_id, status, self);
+
+        let (message, details) = self.message_and_details();
+        let body = ErrorResponse {
+            code: self.code().to_string(),
+            message,
+            details,
+            request_id,
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(e: diesel::result::Error) -> Self {
+        ApiError::Database(e)
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for ApiError {
+    fn from(e: diesel::r2d2::PoolError) -> Self {
+        ApiError::Pool(e)
+    }
+}
+
+impl From<ProviderError> for ApiError {
+    fn from(e: ProviderError) -> Self {
+        ApiError::Provider(e.to_string())
+    }
+}
+
+impl<M: Middleware> From<ContractError<M>> for ApiError {
+    fn from(e: ContractError<M>) -> Self {
+        if let Some(error) = e.decode_contract_revert::<SwitchAssetsErrors>() {
+            ApiError::ContractReverted(describe_revert(&error))
+        } else if let Some(reason) = e.decode_revert::<String>() {
+            ApiError::ContractReverted(reason)
+        } else if e.is_revert() {
+            ApiError::ContractReverted("execution reverted".to_string())
+        } else {
+            ApiError::Contract(e.to_string())
+        }
+    }
+}
+
+// A SwitchAssets revert as its error name and arguments, e.g. "ONLY_OWNER(0x...)"
+pub fn describe_revert(error: &SwitchAssetsErrors) -> String {
+    match error {
+        SwitchAssetsErrors::ADDRESS_ZERO(e) => format!("ADDRESS_ZERO({})", EvmAddress(e.0)),
+        SwitchAssetsErrors::ASSET_ALREADY_EXIST(e) => {
+            format!("ASSET_ALREADY_EXIST(0x{})", hex::encode(e.id))
+        }
+        SwitchAssetsErrors::ASSET_DOES_NOT_EXIST(e) => {
+            format!("ASSET_DOES_NOT_EXIST(0x{})", hex::encode(e.id))
+        }
+        SwitchAssetsErrors::INVALID_TRANSACTION(_) => "INVALID_TRANSACTION()".to_string(),
+        SwitchAssetsErrors::ONLY_OWNER(e) => format!("ONLY_OWNER({})", EvmAddress(e.0)),
+        SwitchAssetsErrors::RevertString(reason) => reason.clone(),
+    }
+}
+
+impl From<hex::FromHexError> for ApiError {
+    fn from(e: hex::FromHexError) -> Self {
+        ApiError::InvalidHex(e)
+    }
+}
+
+impl From<validator::ValidationErrors> for ApiError {
+    fn from(e: validator::ValidationErrors) -> Self {
+        ApiError::Validation(e)
+    }
+}
+
+// Rejections from axum's extractors, whose text says what was wrong with the input
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        ApiError::BadRequest(e.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(e: PathRejection) -> Self {
+        ApiError::BadRequest(e.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        ApiError::BadRequest(e.body_text())
+    }
+}
+
+impl From<eyre::Report> for ApiError {
+    fn from(e: eyre::Report) -> Self {
+        ApiError::Internal(format!("{:?}", e))
+    }
+}
+
+// The id of the request being handled, when called within the request_id middleware
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+fn new_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+// Runs the request with its id in scope for ApiError and returns the id as a header
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,193 @@
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::models::AssetEvent;
+use crate::schema::asset_events;
+use crate::webhooks::enqueue_deliveries;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::dsl::sql;
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{BigInt, Nullable, Text};
+use ethers::types::{H256, U64};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+// How many events a slow live subscriber may fall behind before it has to catch up from the
+// stored log
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+// An event as it went live, numbered in publish order. Live subscribers resume by seq rather
+// than by row id, which is insertion order.
+#[derive(Clone)]
+pub struct PublishedEvent {
+    pub seq: i64,
+    pub event: AssetEvent,
+}
+
+// Which contract event an asset_events row records
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow,
+)]
+#[serde(rename_all = "snake_case")]
+#[diesel(sql_type = Text)]
+pub enum EventKind {
+    Registered,
+    Transferred,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Registered => "registered",
+            EventKind::Transferred => "transferred",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for EventKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for EventKind {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "registered" => Ok(EventKind::Registered),
+            "transferred" => Ok(EventKind::Transferred),
+            other => Err(format!("Unknown event kind: {}", other).into()),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = asset_events)]
+pub struct NewAssetEvent {
+    kind: EventKind,
+    asset_id: AssetId,
+    from_address: Option<EvmAddress>,
+    to_address: EvmAddress,
+    timestamp: i64,
+    block_number: Option<i64>,
+    txn_hash: Option<String>,
+}
+
+impl NewAssetEvent {
+    pub fn registered(
+        asset_id: AssetId,
+        registrant: EvmAddress,
+        registered_at: i64,
+        txn_hash: H256,
+        block_number: Option<U64>,
+    ) -> Self {
+        Self {
+            kind: EventKind::Registered,
+            asset_id,
+            from_address: None,
+            to_address: registrant,
+            timestamp: registered_at,
+            block_number: block_number.map(|block| block.as_u64() as i64),
+            txn_hash: Some(format!("0x{}", hex::encode(txn_hash))),
+        }
+    }
+
+    pub fn transferred(
+        asset_id: AssetId,
+        old_owner: EvmAddress,
+        new_owner: EvmAddress,
+        timestamp: i64,
+        txn_hash: H256,
+        block_number: Option<U64>,
+    ) -> Self {
+        Self {
+            kind: EventKind::Transferred,
+            asset_id,
+            from_address: Some(old_owner),
+            to_address: new_owner,
+            timestamp,
+            block_number: block_number.map(|block| block.as_u64() as i64),
+            txn_hash: Some(format!("0x{}", hex::encode(txn_hash))),
+        }
+    }
+}
+
+// Appends an event to the feed and queues its webhook deliveries. The indexer and the API both
+// see the events of API-sent transactions, so an event that is already recorded is left as
+// is and not delivered twice.
+pub fn record_event(conn: &mut PgConnection, event: &NewAssetEvent) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        let event_id = diesel::insert_into(asset_events::table)
+            .values(event)
+            .on_conflict_do_nothing()
+            .returning(asset_events::id)
+            .get_result::<i32>(conn)
+            .optional()?;
+        if let Some(event_id) = event_id {
+            enqueue_deliveries(
+                conn,
+                event_id,
+                event.kind,
+                event.asset_id,
+                event.from_address,
+                event.to_address,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+// Sends the stored row of an event the indexer has committed to live subscribers, numbering it
+// with the next published_seq. The row may have been recorded earlier by the API that sent the
+// transaction; either way this is the only place events go live, and each goes live once.
+pub fn publish_event(
+    conn: &mut PgConnection,
+    events: &broadcast::Sender<PublishedEvent>,
+    kind: EventKind,
+    asset_id: AssetId,
+    txn_hash: H256,
+) -> QueryResult<()> {
+    let mut query = asset_events::table
+        .filter(asset_events::kind.eq(kind))
+        .filter(asset_events::asset_id.eq(asset_id))
+        .into_boxed();
+    // An asset has one registration, which may predate the recorded transaction hashes
+    if kind == EventKind::Transferred {
+        query = query.filter(asset_events::txn_hash.eq(format!("0x{}", hex::encode(txn_hash))));
+    }
+    let Some(event_id) = query
+        .select(asset_events::id)
+        .first::<i32>(conn)
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    // Already live when the indexer sees the same block again
+    let Some(seq) = diesel::update(
+        asset_events::table
+            .find(event_id)
+            .filter(asset_events::published_seq.is_null()),
+    )
+    .set(asset_events::published_seq.eq(sql::<Nullable<BigInt>>(
+        "nextval('asset_events_published_seq')",
+    )))
+    .returning(asset_events::published_seq.assume_not_null())
+    .get_result::<i64>(conn)
+    .optional()?
+    else {
+        return Ok(());
+    };
+    let event = asset_events::table
+        .find(event_id)
+        .select(AssetEvent::as_select())
+        .first::<AssetEvent>(conn)?;
+
+    // Only fails when nobody is subscribed
+    let _ = events.send(PublishedEvent { seq, event });
+    Ok(())
+}
@@ -1,13 +1,15 @@
+use crate::address::EvmAddress;
+use crate::app_route::SwitchAssetsEvents;
 use crate::app_route::{AssetRegisteredFilter, OwnershipTransferredFilter};
-use crate::app_route::{SwitchAssets, SwitchAssetsEvents};
-use crate::app_state::AppState;
+use crate::app_state::{AppState, SwitchContract};
+use crate::asset_id::AssetId;
+use crate::event_log::{EventKind, NewAssetEvent, publish_event, record_event};
 use crate::handlers::analytics::generate_analytics;
 use crate::schema::{assets, transfers};
+use crate::transactions::record_indexed;
 use chrono::Utc;
 use diesel::prelude::*;
-use ecdsa::SigningKey;
-use ethers::core::k256::Secp256k1;
-use ethers::{core::utils::to_checksum, prelude::*};
+use ethers::prelude::*;
 use eyre::Result;
 use std::sync::Arc;
 
@@ -75,20 +77,44 @@ pub async fn listen_for_events(state: Arc<AppState>) -> Result<()> {
         })?;
         for (event, meta) in asset_registered_logs {
             let txn_hash = Some(format!("0x{}", hex::encode(meta.transaction_hash)));
-            process_asset_registered_event(&contract, &event, conn, txn_hash).await?;
+            process_asset_registered_event(&contract, &event, &meta, conn, txn_hash).await?;
+            if let Err(e) = record_indexed(client.as_ref(), conn, meta.transaction_hash).await {
+                eprintln!("Failed to record indexed transaction: {:?}", e);
+            }
             if let Err(e) = generate_analytics(&state).await {
                 eprintln!("Analytics generation error for AssetRegistered: {:?}", e);
             }
+            if let Err(e) = publish_event(
+                conn,
+                &state.events,
+                EventKind::Registered,
+                AssetId::from(event.asset_id),
+                meta.transaction_hash,
+            ) {
+                eprintln!("Failed to publish registered event: {:?}", e);
+            }
         }
         for (event, meta) in ownership_transferred_logs {
             let txn_hash = Some(format!("0x{}", hex::encode(meta.transaction_hash)));
-            process_ownership_transferred_event(&event, conn, txn_hash)?;
+            process_ownership_transferred_event(&event, &meta, conn, txn_hash)?;
+            if let Err(e) = record_indexed(client.as_ref(), conn, meta.transaction_hash).await {
+                eprintln!("Failed to record indexed transaction: {:?}", e);
+            }
             if let Err(e) = generate_analytics(&state).await {
                 eprintln!(
                     "Analytics generation error for OwnershipTransferred: {:?}",
                     e
                 );
             }
+            if let Err(e) = publish_event(
+                conn,
+                &state.events,
+                EventKind::Transferred,
+                AssetId::from(event.asset_id),
+                meta.transaction_hash,
+            ) {
+                eprintln!("Failed to publish transferred event: {:?}", e);
+            }
         }
 
         current_block = to_block + 1;
@@ -110,10 +136,22 @@ pub async fn listen_for_events(state: Arc<AppState>) -> Result<()> {
                     eprintln!("Failed to get DB connection: {:?}", e);
                     eyre::eyre!("Failed to get DB connection: {}", e)
                 })?;
-                process_asset_registered_event(&contract, &event, conn, txn_hash).await?;
+                process_asset_registered_event(&contract, &event, &meta, conn, txn_hash).await?;
+                if let Err(e) = record_indexed(client.as_ref(), conn, meta.transaction_hash).await {
+                    eprintln!("Failed to record indexed transaction: {:?}", e);
+                }
                 if let Err(e) = generate_analytics(&state).await {
                     eprintln!("Analytics generation error for AssetRegistered: {:?}", e);
                 }
+                if let Err(e) = publish_event(
+                    conn,
+                    &state.events,
+                    EventKind::Registered,
+                    AssetId::from(event.asset_id),
+                    meta.transaction_hash,
+                ) {
+                    eprintln!("Failed to publish registered event: {:?}", e);
+                }
             }
             Some(Ok((SwitchAssetsEvents::OwnershipTransferredFilter(event), meta))) => {
                 let txn_hash = Some(format!("0x{}", hex::encode(meta.transaction_hash)));
@@ -121,13 +159,25 @@ pub async fn listen_for_events(state: Arc<AppState>) -> Result<()> {
                     eprintln!("Failed to get DB connection: {:?}", e);
                     eyre::eyre!("Failed to get DB connection: {}", e)
                 })?;
-                process_ownership_transferred_event(&event, conn, txn_hash)?;
+                process_ownership_transferred_event(&event, &meta, conn, txn_hash)?;
+                if let Err(e) = record_indexed(client.as_ref(), conn, meta.transaction_hash).await {
+                    eprintln!("Failed to record indexed transaction: {:?}", e);
+                }
                 if let Err(e) = generate_analytics(&state).await {
                     eprintln!(
                         "Analytics generation error for OwnershipTransferred: {:?}",
                         e
                     );
                 }
+                if let Err(e) = publish_event(
+                    conn,
+                    &state.events,
+                    EventKind::Transferred,
+                    AssetId::from(event.asset_id),
+                    meta.transaction_hash,
+                ) {
+                    eprintln!("Failed to publish transferred event: {:?}", e);
+                }
             }
             Some(Err(e)) => {
                 eprintln!("Event stream error: {:?}", e);
@@ -144,13 +194,14 @@ pub async fn listen_for_events(state: Arc<AppState>) -> Result<()> {
 }
 
 async fn process_asset_registered_event(
-    contract: &SwitchAssets<SignerMiddleware<Provider<Http>, Wallet<SigningKey<Secp256k1>>>>,
+    contract: &SwitchContract,
     event: &AssetRegisteredFilter,
+    meta: &LogMeta,
     conn: &mut PgConnection,
     txn_hash: Option<String>,
 ) -> Result<()> {
     let asset_id = format!("0x{}", hex::encode(event.asset_id));
-    let owner = to_checksum(&event.asset_owner, None);
+    let owner = EvmAddress(event.asset_owner).to_string();
 
     let asset = contract
         .method::<_, (H256, H160, String, U256)>("getAsset", event.asset_id)?
@@ -169,57 +220,76 @@ async fn process_asset_registered_event(
     let description = asset.2;
     let registered_at = asset.3.as_u64() as i64;
 
-    // Check if asset exists with this txn_hash
-    if let Some(ref txn) = txn_hash {
-        let exists: bool = assets::table
-            .filter(assets::asset_id.eq(&asset_id))
-            // .filter(assets::txn_hash.eq(txn))
-            .select(diesel::dsl::count_star())
-            .first::<i64>(conn)
-            .map(|count| count > 0)
-            .map_err(|e| {
-                eprintln!("Failed to check existing asset {}: {:?}", asset_id, e);
-                eyre::eyre!("Failed to check existing asset: {}", e)
-            })?;
+    // The event row and its webhook outbox are only committed together with the asset row
+    conn.transaction(|conn| {
+        record_event(
+            conn,
+            &NewAssetEvent::registered(
+                AssetId::from(event.asset_id),
+                EvmAddress(event.asset_owner),
+                registered_at,
+                meta.transaction_hash,
+                Some(meta.block_number),
+            ),
+        )
+        .map_err(|e| {
+            eprintln!("Failed to record registration of {}: {:?}", asset_id, e);
+            eyre::eyre!("Failed to record registration: {}", e)
+        })?;
 
-        if exists {
-            eprintln!(
-                "Skipping duplicate asset registration for {} (tx: {})",
-                asset_id, txn
-            );
-            return Ok(());
+        // Check if asset exists with this txn_hash
+        if let Some(ref txn) = txn_hash {
+            let exists: bool = assets::table
+                .filter(assets::asset_id.eq(&asset_id))
+                // .filter(assets::txn_hash.eq(txn))
+                .select(diesel::dsl::count_star())
+                .first::<i64>(conn)
+                .map(|count| count > 0)
+                .map_err(|e| {
+                    eprintln!("Failed to check existing asset {}: {:?}", asset_id, e);
+                    eyre::eyre!("Failed to check existing asset: {}", e)
+                })?;
+
+            if exists {
+                eprintln!(
+                    "Skipping duplicate asset registration for {} (tx: {})",
+                    asset_id, txn
+                );
+                return Ok(());
+            }
         }
-    }
 
-    // Insert or update the asset table
-    let db_asset = (
-        assets::asset_id.eq(&asset_id),
-        assets::owner.eq(&owner),
-        assets::description.eq(&description),
-        assets::registered_at.eq(registered_at),
-    );
-    diesel::insert_into(assets::table)
-        .values(&db_asset)
-        .on_conflict(assets::asset_id)
-        .do_update()
-        .set(db_asset)
-        .execute(conn)
-        .map_err(|e| {
-            eprintln!("Failed to insert/update asset {}: {:?}", asset_id, e);
-            eyre::eyre!("Failed to insert/update asset: {}", e)
-        })?;
+        // Insert or update the asset table
+        let db_asset = (
+            assets::asset_id.eq(&asset_id),
+            assets::owner.eq(&owner),
+            assets::description.eq(&description),
+            assets::registered_at.eq(registered_at),
+        );
+        diesel::insert_into(assets::table)
+            .values(&db_asset)
+            .on_conflict(assets::asset_id)
+            .do_update()
+            .set(db_asset)
+            .execute(conn)
+            .map_err(|e| {
+                eprintln!("Failed to insert/update asset {}: {:?}", asset_id, e);
+                eyre::eyre!("Failed to insert/update asset: {}", e)
+            })?;
 
-    Ok(())
+        Ok(())
+    })
 }
 
 fn process_ownership_transferred_event(
     event: &OwnershipTransferredFilter,
+    meta: &LogMeta,
     conn: &mut PgConnection,
     txn_hash: Option<String>,
 ) -> Result<()> {
     let asset_id = format!("0x{}", hex::encode(event.asset_id));
-    let old_owner = to_checksum(&event.old_owner, None);
-    let new_owner = to_checksum(&event.new_owner, None);
+    let old_owner = EvmAddress(event.old_owner).to_string();
+    let new_owner = EvmAddress(event.new_owner).to_string();
     let timestamp = Utc::now().timestamp();
 
     // Check if transfer exists
@@ -264,6 +334,22 @@ fn process_ownership_transferred_event(
                 eyre::eyre!("Failed to insert transfer: {}", e)
             })?;
 
+        record_event(
+            conn,
+            &NewAssetEvent::transferred(
+                AssetId::from(event.asset_id),
+                EvmAddress(event.old_owner),
+                EvmAddress(event.new_owner),
+                timestamp,
+                meta.transaction_hash,
+                Some(meta.block_number),
+            ),
+        )
+        .map_err(|e| {
+            eprintln!("Failed to record transfer of asset {}: {:?}", asset_id, e);
+            eyre::eyre!("Failed to record transfer: {}", e)
+        })?;
+
         // Update asset owner
         diesel::update(assets::table)
             .filter(assets::asset_id.eq(&asset_id))
@@ -0,0 +1,170 @@
+use crate::error::ApiError;
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::request::Parts;
+use serde::de::DeserializeOwned;
+
+// axum's Json, Path and Query extractors with their rejections turned into ApiError, so a
+// malformed body, path segment or query string is answered with the same error body (code,
+// message, request_id) as any other failure rather than axum's plain-text one.
+
+pub struct ApiJson<T>(pub T);
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for ApiJson<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+pub struct ApiPath<T>(pub T);
+
+impl<T: DeserializeOwned + Send, S: Send + Sync> FromRequestParts<S> for ApiPath<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) =
+            axum::extract::Path::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiPath(value))
+    }
+}
+
+pub struct ApiQuery<T>(pub T);
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for ApiQuery<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) =
+            axum::extract::Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::EvmAddress;
+    use crate::asset_id::AssetId;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Input {
+        description: String,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Params {
+        address: EvmAddress,
+    }
+
+    async fn error_body(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn json_error(content_type: &str, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", content_type)
+            .body(Body::from(body))
+            .unwrap();
+        match ApiJson::<Input>::from_request(request, &()).await {
+            Ok(_) => panic!("{} was accepted", body),
+            Err(error) => error_body(error).await,
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_an_api_error() {
+        for (content_type, body) in [
+            ("application/json", "{\"description\":"),
+            ("application/json", "{\"name\": \"x\"}"),
+            ("text/plain", "{\"description\": \"x\"}"),
+        ] {
+            let (status, error) = json_error(content_type, body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+            assert_eq!(error["code"], "bad_request");
+            assert!(!error["message"].as_str().unwrap().is_empty());
+            assert!(!error["request_id"].as_str().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_asset_id_in_path_is_an_api_error() {
+        async fn handler(ApiPath(asset_id): ApiPath<AssetId>) -> String {
+            asset_id.to_string()
+        }
+        let app = Router::new().route("/assets/{asset_id}", get(handler));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/assets/{}", "AB".repeat(32)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/assets/0x1234")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error["code"], "bad_request");
+        assert!(
+            error["message"]
+                .as_str()
+                .unwrap()
+                .contains("Invalid asset_id length")
+        );
+        assert!(!error["request_id"].as_str().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_query_is_an_api_error() {
+        let (mut parts, _) = Request::builder()
+            .uri("/?address=0x1234")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let Err(error) = ApiQuery::<Params>::from_request_parts(&mut parts, &()).await else {
+            panic!("short address was accepted");
+        };
+        let (status, error) = error_body(error).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["code"], "bad_request");
+        assert!(
+            error["message"]
+                .as_str()
+                .unwrap()
+                .contains("address length")
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::models::{ApiResponse, Asset, Transfer};
+use crate::pagination::{
+    AssetPageParams, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT, TransferPageParams, load_asset_page,
+    load_transfer_page,
+};
+use crate::schema::{assets, transfers};
+use crate::text_search::{matches, parse_query};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, InputObject, Object, Result, Schema, SimpleObject,
+};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use std::collections::HashMap;
+
+pub type SwitchSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// Deeper queries than this are refused before any resolver runs, so a client cannot walk
+// asset -> owner -> assets -> ... without bound
+const MAX_QUERY_DEPTH: usize = 12;
+// Likewise for queries whose nested pages could return too many rows in total. Every field
+// costs 1, and a paged field costs its selection times its page size (see page_complexity).
+const MAX_QUERY_COMPLEXITY: usize = 10_000;
+
+async_graphql::scalar!(
+    AssetId,
+    "AssetId",
+    "32-byte asset id as 0x-prefixed hex, returned lowercase"
+);
+async_graphql::scalar!(
+    EvmAddress,
+    "EvmAddress",
+    "20-byte address as 0x-prefixed hex, returned lowercase. Mixed case input must be a valid EIP-55 checksum."
+);
+
+pub fn build_schema() -> SwitchSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+// Batches the relationship lookups of one request. Assets by id are loaded in a single query
+// for every key asked for while a level of the query resolves; relationship lists are paged
+// per parent, one query each but all on one connection. A loader lives for one request, and
+// caches within it.
+pub struct DbLoader {
+    pub db_pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl DbLoader {
+    pub fn new(db_pool: Pool<ConnectionManager<PgConnection>>) -> DataLoader<DbLoader> {
+        DataLoader::new(DbLoader { db_pool }, tokio::spawn)
+    }
+}
+
+// The `first` and `after` arguments of a relationship field
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PageArgs {
+    first: Option<i32>,
+    after: Option<String>,
+}
+
+// One page of a relationship list
+#[derive(Clone)]
+pub struct Page<T> {
+    nodes: Vec<T>,
+    total_count: i64,
+    next_cursor: Option<String>,
+}
+
+impl<T> From<ApiResponse<Vec<T>>> for Page<T> {
+    fn from(page: ApiResponse<Vec<T>>) -> Self {
+        Page {
+            nodes: page.data,
+            total_count: page.total.unwrap_or_default(),
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetById(AssetId);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TransfersOfAsset(AssetId, PageArgs);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AssetsOfOwner(EvmAddress, PageArgs);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TransfersOfOwner(EvmAddress, PageArgs);
+
+impl Loader<AssetById> for DbLoader {
+    type Value = Asset;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[AssetById]) -> Result<HashMap<AssetById, Asset>> {
+        let conn = &mut self.db_pool.get()?;
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        Ok(assets::table
+            .filter(assets::asset_id.eq_any(ids))
+            .load::<Asset>(conn)?
+            .into_iter()
+            .map(|asset| (AssetById(asset.asset_id), asset))
+            .collect())
+    }
+}
+
+impl Loader<TransfersOfAsset> for DbLoader {
+    type Value = Page<Transfer>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[TransfersOfAsset],
+    ) -> Result<HashMap<TransfersOfAsset, Page<Transfer>>> {
+        let conn = &mut self.db_pool.get()?;
+        let mut pages = HashMap::new();
+        for key in keys {
+            let TransfersOfAsset(asset_id, page) = key;
+            let filtered = || {
+                transfers::table
+                    .filter(transfers::asset_id.eq(*asset_id))
+                    .into_boxed()
+            };
+            let page = load_transfer_page(conn, filtered, &page.transfer_params())?;
+            pages.insert(key.clone(), page.into());
+        }
+        Ok(pages)
+    }
+}
+
+impl Loader<AssetsOfOwner> for DbLoader {
+    type Value = Page<Asset>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[AssetsOfOwner]) -> Result<HashMap<AssetsOfOwner, Page<Asset>>> {
+        let conn = &mut self.db_pool.get()?;
+        let mut pages = HashMap::new();
+        for key in keys {
+            let AssetsOfOwner(owner, page) = key;
+            let filtered = || assets::table.filter(assets::owner.eq(*owner)).into_boxed();
+            let page = load_asset_page(conn, filtered, &page.asset_params())?;
+            pages.insert(key.clone(), page.into());
+        }
+        Ok(pages)
+    }
+}
+
+impl Loader<TransfersOfOwner> for DbLoader {
+    type Value = Page<Transfer>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[TransfersOfOwner],
+    ) -> Result<HashMap<TransfersOfOwner, Page<Transfer>>> {
+        let conn = &mut self.db_pool.get()?;
+        let mut pages = HashMap::new();
+        for key in keys {
+            let TransfersOfOwner(owner, page) = key;
+            let filtered = || {
+                transfers::table
+                    .filter(
+                        transfers::old_owner
+                            .eq(*owner)
+                            .or(transfers::new_owner.eq(*owner)),
+                    )
+                    .into_boxed()
+            };
+            let page = load_transfer_page(conn, filtered, &page.transfer_params())?;
+            pages.insert(key.clone(), page.into());
+        }
+        Ok(pages)
+    }
+}
+
+impl PageArgs {
+    fn asset_params(&self) -> AssetPageParams {
+        AssetPageParams {
+            cursor: self.after.clone(),
+            limit: self.first.map(i64::from),
+            sort: None,
+            direction: None,
+        }
+    }
+
+    fn transfer_params(&self) -> TransferPageParams {
+        TransferPageParams {
+            cursor: self.after.clone(),
+            limit: self.first.map(i64::from),
+            sort: None,
+            direction: None,
+        }
+    }
+}
+
+// What a paged field costs against MAX_QUERY_COMPLEXITY: its selection once per node it may
+// return, so a query is refused up front if its nested pages could add up to too many rows
+fn page_complexity(first: Option<i32>, child_complexity: usize) -> usize {
+    let limit = first
+        .map(i64::from)
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    (limit as usize).saturating_mul(child_complexity)
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<DbLoader> {
+    ctx.data_unchecked::<DataLoader<DbLoader>>()
+}
+
+// An indexed asset
+pub struct AssetNode(Asset);
+
+#[Object(name = "Asset")]
+impl AssetNode {
+    async fn asset_id(&self) -> AssetId {
+        self.0.asset_id
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    /// Registration time, unix seconds
+    async fn registered_at(&self) -> i64 {
+        self.0.registered_at
+    }
+
+    /// Current owner
+    async fn owner(&self) -> OwnerNode {
+        OwnerNode(self.0.owner)
+    }
+
+    /// Transfers of the asset, oldest first, paginated like the root `transfers`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn transfers(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<TransferConnection> {
+        let page = loader(ctx)
+            .load_one(TransfersOfAsset(self.0.asset_id, PageArgs { first, after }))
+            .await?;
+        Ok(TransferConnection::from_page(page))
+    }
+}
+
+// An indexed ownership transfer
+pub struct TransferNode(Transfer);
+
+#[Object(name = "Transfer")]
+impl TransferNode {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn asset_id(&self) -> AssetId {
+        self.0.asset_id
+    }
+
+    /// The transferred asset
+    async fn asset(&self, ctx: &Context<'_>) -> Result<Option<AssetNode>> {
+        Ok(loader(ctx)
+            .load_one(AssetById(self.0.asset_id))
+            .await?
+            .map(AssetNode))
+    }
+
+    async fn old_owner(&self) -> OwnerNode {
+        OwnerNode(self.0.old_owner)
+    }
+
+    async fn new_owner(&self) -> OwnerNode {
+        OwnerNode(self.0.new_owner)
+    }
+
+    /// Time the transfer was indexed, unix seconds
+    async fn timestamp(&self) -> i64 {
+        self.0.timestamp
+    }
+
+    async fn txn_hash(&self) -> &str {
+        &self.0.txn_hash
+    }
+}
+
+// Any address, whether or not it ever held an asset
+pub struct OwnerNode(EvmAddress);
+
+#[Object(name = "Owner")]
+impl OwnerNode {
+    async fn address(&self) -> EvmAddress {
+        self.0
+    }
+
+    /// Assets the address holds now, oldest registration first, paginated like the root
+    /// `assets`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn assets(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<AssetConnection> {
+        let page = loader(ctx)
+            .load_one(AssetsOfOwner(self.0, PageArgs { first, after }))
+            .await?;
+        Ok(AssetConnection::from_page(page))
+    }
+
+    /// Transfers the address sent or received, oldest first, paginated like the root
+    /// `transfers`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn transfers(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<TransferConnection> {
+        let page = loader(ctx)
+            .load_one(TransfersOfOwner(self.0, PageArgs { first, after }))
+            .await?;
+        Ok(TransferConnection::from_page(page))
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    /// Pass as `after` to fetch the next page
+    pub end_cursor: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct AssetConnection {
+    pub nodes: Vec<AssetNode>,
+    /// Assets matching the filter across all pages
+    pub total_count: i64,
+    pub page_info: PageInfo,
+}
+
+#[derive(SimpleObject)]
+pub struct TransferConnection {
+    pub nodes: Vec<TransferNode>,
+    /// Transfers matching the filter across all pages
+    pub total_count: i64,
+    pub page_info: PageInfo,
+}
+
+impl AssetConnection {
+    fn from_page(page: Option<Page<Asset>>) -> Self {
+        let page = page.unwrap_or_else(Page::empty);
+        AssetConnection {
+            nodes: page.nodes.into_iter().map(AssetNode).collect(),
+            total_count: page.total_count,
+            page_info: PageInfo::after(page.next_cursor),
+        }
+    }
+}
+
+impl TransferConnection {
+    fn from_page(page: Option<Page<Transfer>>) -> Self {
+        let page = page.unwrap_or_else(Page::empty);
+        TransferConnection {
+            nodes: page.nodes.into_iter().map(TransferNode).collect(),
+            total_count: page.total_count,
+            page_info: PageInfo::after(page.next_cursor),
+        }
+    }
+}
+
+impl PageInfo {
+    fn after(next_cursor: Option<String>) -> Self {
+        PageInfo {
+            has_next_page: next_cursor.is_some(),
+            end_cursor: next_cursor,
+        }
+    }
+}
+
+impl<T> Page<T> {
+    fn empty() -> Self {
+        Page {
+            nodes: Vec::new(),
+            total_count: 0,
+            next_cursor: None,
+        }
+    }
+}
+
+// Every field is optional and they combine with AND, as in POST /search
+#[derive(InputObject, Default)]
+pub struct AssetFilter {
+    pub owner: Option<EvmAddress>,
+    /// Full-text search over descriptions, same syntax as POST /search
+    pub q: Option<String>,
+    /// Registered at or after, unix seconds
+    pub start_date: Option<i64>,
+    /// Registered at or before, unix seconds
+    pub end_date: Option<i64>,
+}
+
+#[derive(InputObject, Default)]
+pub struct TransferFilter {
+    pub asset_id: Option<AssetId>,
+    /// Sender
+    pub old_owner: Option<EvmAddress>,
+    /// Receiver
+    pub new_owner: Option<EvmAddress>,
+    /// Either side
+    pub address: Option<EvmAddress>,
+    /// Indexed at or after, unix seconds
+    pub start_date: Option<i64>,
+    /// Indexed at or before, unix seconds
+    pub end_date: Option<i64>,
+}
+
+pub struct QueryRoot;
+
+#[Object(name = "Query")]
+impl QueryRoot {
+    /// An indexed asset by id
+    async fn asset(&self, ctx: &Context<'_>, asset_id: AssetId) -> Result<Option<AssetNode>> {
+        Ok(loader(ctx)
+            .load_one(AssetById(asset_id))
+            .await?
+            .map(AssetNode))
+    }
+
+    /// Indexed assets, oldest registration first. Pages take the same `first` limits and
+    /// cursors as the REST lists.
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn assets(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<AssetFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<AssetConnection> {
+        let filter = filter.unwrap_or_default();
+        let tsquery = filter.q.as_deref().map(parse_query).transpose()?;
+        let conn = &mut loader(ctx).loader().db_pool.get()?;
+
+        let filtered = || {
+            let mut query = assets::table.into_boxed();
+            if let Some(owner) = filter.owner {
+                query = query.filter(assets::owner.eq(owner));
+            }
+            if let Some(start_date) = filter.start_date {
+                query = query.filter(assets::registered_at.ge(start_date));
+            }
+            if let Some(end_date) = filter.end_date {
+                query = query.filter(assets::registered_at.le(end_date));
+            }
+            if let Some(tsquery) = &tsquery {
+                query = query.filter(matches(tsquery));
+            }
+            query
+        };
+        let page = load_asset_page(
+            conn,
+            filtered,
+            &AssetPageParams {
+                cursor: after,
+                limit: first.map(i64::from),
+                sort: None,
+                direction: None,
+            },
+        )?;
+
+        Ok(AssetConnection::from_page(Some(page.into())))
+    }
+
+    /// Indexed transfers, oldest first, paginated like `assets`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn transfers(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TransferFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<TransferConnection> {
+        let filter = filter.unwrap_or_default();
+        let conn = &mut loader(ctx).loader().db_pool.get()?;
+
+        let filtered = || {
+            let mut query = transfers::table.into_boxed();
+            if let Some(asset_id) = filter.asset_id {
+                query = query.filter(transfers::asset_id.eq(asset_id));
+            }
+            if let Some(old_owner) = filter.old_owner {
+                query = query.filter(transfers::old_owner.eq(old_owner));
+            }
+            if let Some(new_owner) = filter.new_owner {
+                query = query.filter(transfers::new_owner.eq(new_owner));
+            }
+            if let Some(address) = filter.address {
+                query = query.filter(
+                    transfers::old_owner
+                        .eq(address)
+                        .or(transfers::new_owner.eq(address)),
+                );
+            }
+            if let Some(start_date) = filter.start_date {
+                query = query.filter(transfers::timestamp.ge(start_date));
+            }
+            if let Some(end_date) = filter.end_date {
+                query = query.filter(transfers::timestamp.le(end_date));
+            }
+            query
+        };
+        let page = load_transfer_page(
+            conn,
+            filtered,
+            &TransferPageParams {
+                cursor: after,
+                limit: first.map(i64::from),
+                sort: None,
+                direction: None,
+            },
+        )?;
+
+        Ok(TransferConnection::from_page(Some(page.into())))
+    }
+
+    /// An address and what it holds and has transferred
+    async fn owner(&self, address: EvmAddress) -> OwnerNode {
+        OwnerNode(address)
+    }
+}
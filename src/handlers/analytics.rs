@@ -1,4 +1,5 @@
 use crate::app_state::AppState;
+use crate::error::{ApiError, ErrorResponse};
 use crate::models::{Analytics, TopOwner};
 use crate::schema::{assets, transfers};
 use axum::Json;
@@ -15,20 +16,16 @@ use utoipa::OpenApi;
     path = "/analytics",
     responses(
         (status = 200, description = "Analytics data retrieved successfully", body = Analytics),
-        (status = 500, description = "Internal server error")
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
-pub async fn get_analytics() -> Result<Json<Analytics>, axum::http::StatusCode> {
-    let file = File::open("src/files/analytics.json").map_err(|e| {
-        eprintln!("Failed to open analytics.json: {:?}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+pub async fn get_analytics() -> Result<Json<Analytics>, ApiError> {
+    let file = File::open("src/files/analytics.json")
+        .map_err(|e| ApiError::internal(format!("Failed to open analytics.json: {:?}", e)))?;
     let reader = BufReader::new(file);
-    let analytics: Analytics = serde_json::from_reader(reader).map_err(|e| {
-        eprintln!("Failed to parse analytics.json: {:?}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let analytics: Analytics = serde_json::from_reader(reader)
+        .map_err(|e| ApiError::internal(format!("Failed to parse analytics.json: {:?}", e)))?;
     Ok(Json(analytics))
 }
 
@@ -0,0 +1,173 @@
+use crate::auth::{generate_key, hash_key, key_prefix};
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiJson, ApiPath, ApiQuery};
+use crate::{
+    app_state::AppState,
+    models::{ApiKey, ApiKeyResponse, ApiKeyWrite, ApiResponse, CreateApiKeyInput},
+    schema::{api_key_writes, api_keys},
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+const DEFAULT_WRITE_LIMIT: i64 = 50;
+const MAX_WRITE_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApiKeyWriteParams {
+    /// Newest writes to return, default 50, at most 500
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api_keys",
+    request_body(content = CreateApiKeyInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Key created. The key itself is only returned here.", body = ApiResponse<ApiKeyResponse>),
+        (status = 400, description = "Invalid name or role", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<CreateApiKeyInput>,
+) -> Result<Json<ApiResponse<ApiKeyResponse>>, ApiError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("API key name must not be empty"));
+    }
+    let key = generate_key();
+
+    let conn = &mut state.db_pool.get()?;
+    let api_key = diesel::insert_into(api_keys::table)
+        .values((
+            api_keys::name.eq(name),
+            api_keys::key_prefix.eq(key_prefix(&key)),
+            api_keys::key_hash.eq(hash_key(&key)),
+            api_keys::role.eq(input.role),
+            api_keys::created_at.eq(chrono::Utc::now().timestamp()),
+        ))
+        .returning(ApiKey::as_returning())
+        .get_result::<ApiKey>(conn)?;
+
+    Ok(Json(ApiResponse::new(ApiKeyResponse {
+        key: Some(key),
+        ..api_key.into()
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api_keys",
+    responses(
+        (status = 200, description = "All API keys, revoked ones included", body = ApiResponse<Vec<ApiKeyResponse>>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<Vec<ApiKeyResponse>>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let keys = api_keys::table
+        .order(api_keys::id.asc())
+        .select(ApiKey::as_select())
+        .load::<ApiKey>(conn)?;
+
+    Ok(Json(ApiResponse::new(
+        keys.into_iter().map(Into::into).collect(),
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api_keys/{id}",
+    params(("id" = i32, Path, description = "API key id")),
+    responses(
+        (status = 200, description = "Key revoked; it is kept, with its write log, but no longer authenticates", body = ApiResponse<ApiKeyResponse>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = ErrorResponse),
+        (status = 404, description = "No such API key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn revoke_api_key(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<ApiKeyResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    diesel::update(
+        api_keys::table
+            .find(id)
+            .filter(api_keys::revoked_at.is_null()),
+    )
+    .set(api_keys::revoked_at.eq(chrono::Utc::now().timestamp()))
+    .execute(conn)?;
+    // Revoking twice keeps the first revocation time
+    let api_key = api_keys::table
+        .find(id)
+        .select(ApiKey::as_select())
+        .first::<ApiKey>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("API key {} not found", id)))?;
+
+    Ok(Json(ApiResponse::new(api_key.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api_keys/{id}/writes",
+    params(
+        ("id" = i32, Path, description = "API key id"),
+        ApiKeyWriteParams
+    ),
+    responses(
+        (status = 200, description = "Requests the key made to write and admin endpoints, newest first", body = ApiResponse<Vec<ApiKeyWrite>>),
+        (status = 400, description = "Invalid limit", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = ErrorResponse),
+        (status = 404, description = "No such API key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn list_api_key_writes(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+    ApiQuery(params): ApiQuery<ApiKeyWriteParams>,
+) -> Result<Json<ApiResponse<Vec<ApiKeyWrite>>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_WRITE_LIMIT);
+    if !(1..=MAX_WRITE_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit {} outside of 1..={}",
+            limit, MAX_WRITE_LIMIT
+        )));
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    api_keys::table
+        .find(id)
+        .select(api_keys::id)
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("API key {} not found", id)))?;
+
+    let writes = api_key_writes::table
+        .filter(api_key_writes::api_key_id.eq(id))
+        .order(api_key_writes::id.desc())
+        .limit(limit)
+        .select(ApiKeyWrite::as_select())
+        .load::<ApiKeyWrite>(conn)?;
+
+    Ok(Json(ApiResponse::new(writes)))
+}
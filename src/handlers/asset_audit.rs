@@ -0,0 +1,118 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiJson, ApiPath};
+use crate::{
+    app_state::AppState,
+    asset_id::{AssetId, derive_asset_id, verify_asset},
+    models::{
+        ApiResponse, AssetAuditResponse, AssetVerification, DeriveAssetIdInput,
+        DerivedAssetIdResponse, RawAsset,
+    },
+    schema::{assets, transfers},
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use ethers::types::U256;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[utoipa::path(
+    post,
+    path = "/assets/derive_id",
+    request_body(content = DeriveAssetIdInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Asset id the contract would assign to this registration", body = ApiResponse<DerivedAssetIdResponse>),
+        (status = 400, description = "Invalid owner address", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn derive_id(
+    ApiJson(input): ApiJson<DeriveAssetIdInput>,
+) -> Result<Json<ApiResponse<DerivedAssetIdResponse>>, ApiError> {
+    let asset_id = derive_asset_id(
+        input.owner.0,
+        U256::from(input.registered_at),
+        &input.description,
+    );
+    Ok(Json(ApiResponse::new(DerivedAssetIdResponse {
+        asset_id: AssetId(asset_id),
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/assets/{asset_id}/verify",
+    params(("asset_id" = String, Path, description = "Asset ID")),
+    responses(
+        (status = 200, description = "Whether the stored asset's id matches its registration data", body = ApiResponse<AssetVerification>),
+        (status = 404, description = "Asset not indexed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn verify_asset_id(
+    ApiPath(asset_id): ApiPath<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AssetVerification>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let asset = assets::table
+        .find(&asset_id)
+        .select(RawAsset::as_select())
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Asset not indexed: {}", asset_id)))?;
+    let first_old_owner = transfers::table
+        .filter(transfers::asset_id.eq(&asset_id))
+        .order(transfers::id.asc())
+        .select(transfers::old_owner)
+        .first::<String>(conn)
+        .optional()?;
+
+    Ok(Json(ApiResponse::new(verify_asset(
+        &asset,
+        first_old_owner.as_deref(),
+    ))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/assets/audit",
+    responses(
+        (status = 200, description = "Every indexed asset whose id does not match its registration data", body = ApiResponse<AssetAuditResponse>),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn audit_assets(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AssetAuditResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let all_assets = assets::table.select(RawAsset::as_select()).load(conn)?;
+    // Oldest transfer first, so the first entry kept per asset names its registrant
+    let mut first_old_owners = HashMap::new();
+    for (asset_id, old_owner) in transfers::table
+        .order(transfers::id.asc())
+        .select((transfers::asset_id, transfers::old_owner))
+        .load::<(String, String)>(conn)?
+    {
+        first_old_owners.entry(asset_id).or_insert(old_owner);
+    }
+
+    let mismatches = all_assets
+        .iter()
+        .map(|asset| {
+            verify_asset(
+                asset,
+                first_old_owners.get(&asset.asset_id).map(String::as_str),
+            )
+        })
+        .filter(|verification| !verification.valid)
+        .collect::<Vec<_>>();
+
+    Ok(Json(ApiResponse::new(AssetAuditResponse {
+        checked: all_assets.len(),
+        inconsistent: mismatches.len(),
+        mismatches,
+    })))
+}
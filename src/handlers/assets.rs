@@ -1,9 +1,14 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiPath, ApiQuery};
 use crate::{
+    address::EvmAddress,
     app_state::AppState,
-    models::{ApiResponse, Asset},
-    schema::assets,
+    asset_id::AssetId,
+    event_log::EventKind,
+    models::{ApiResponse, Asset, AssetDetailResponse, AssetRegistration, ProvenanceHop, Transfer},
+    pagination::{AssetPageParams, load_asset_page},
+    schema::{asset_events, assets, transactions, transfers},
 };
-use axum::http::StatusCode;
 use axum::{Json, extract::State};
 use diesel::prelude::*;
 use std::sync::Arc;
@@ -12,25 +17,113 @@ use utoipa::path;
 #[utoipa::path(
     get,
     path = "/assets",
+    params(AssetPageParams),
     responses(
-        (status = 200, description = "List all registered assets", body = ApiResponse<Vec<Asset>>),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "One page of registered assets", body = ApiResponse<Vec<Asset>>),
+        (status = 400, description = "Invalid cursor, limit or sort", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 pub async fn get_all_assets(
     State(state): State<Arc<AppState>>,
-) -> eyre::Result<Json<ApiResponse<Vec<Asset>>>, StatusCode> {
-    use self::assets::dsl::*;
-    let conn = &mut state.db_pool.get().map_err(|e| {
-        eprintln!("DB connection error: {:?}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let results = assets.load::<Asset>(conn).map_err(|e| {
-        eprintln!("Assets query error: {:?}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    Ok(Json(ApiResponse { data: results }))
+    ApiQuery(page): ApiQuery<AssetPageParams>,
+) -> eyre::Result<Json<ApiResponse<Vec<Asset>>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let results = load_asset_page(conn, || assets::table.into_boxed(), &page)?;
+
+    Ok(Json(results))
+}
+
+#[utoipa::path(
+    get,
+    path = "/assets/{asset_id}",
+    params(("asset_id" = AssetId, Path, description = "Asset ID")),
+    responses(
+        (status = 200, description = "The asset with its registration and full chain of custody", body = ApiResponse<AssetDetailResponse>),
+        (status = 400, description = "Invalid asset ID", body = ErrorResponse),
+        (status = 404, description = "Asset not indexed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_asset_detail(
+    ApiPath(asset_id): ApiPath<AssetId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AssetDetailResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let asset = assets::table
+        .find(asset_id)
+        .first::<Asset>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Asset not indexed: {}", asset_id)))?;
+    let registered = asset_events::table
+        .filter(asset_events::kind.eq(EventKind::Registered))
+        .filter(asset_events::asset_id.eq(asset_id))
+        .select((
+            asset_events::to_address,
+            asset_events::txn_hash,
+            asset_events::block_number,
+        ))
+        .first::<(EvmAddress, Option<String>, Option<i64>)>(conn)
+        .optional()?;
+
+    let mut hops = transfers::table
+        .left_join(transactions::table)
+        .filter(transfers::asset_id.eq(asset_id))
+        .select((
+            Transfer::as_select(),
+            transactions::block_number.nullable(),
+            transactions::block_timestamp.nullable(),
+        ))
+        .load::<(Transfer, Option<i64>, Option<i64>)>(conn)?;
+    // Chain order: block time where it was recorded, otherwise indexing time, and the order
+    // the transfers were indexed in within the same second
+    hops.sort_by_key(|(transfer, _, block_timestamp)| {
+        (block_timestamp.unwrap_or(transfer.timestamp), transfer.id)
+    });
+
+    // Registrations indexed before they were recorded as events fall back to the first owner
+    // the transfers show
+    let (registrant, txn_hash, block_number) = registered.unwrap_or_else(|| {
+        let registrant = hops
+            .first()
+            .map(|(transfer, _, _)| transfer.old_owner)
+            .unwrap_or(asset.owner);
+        (registrant, None, None)
+    });
+
+    let mut held_since = asset.registered_at;
+    let provenance = hops
+        .into_iter()
+        .map(|(transfer, block_number, block_timestamp)| {
+            let time = block_timestamp.unwrap_or(transfer.timestamp);
+            let hop = ProvenanceHop {
+                old_owner: transfer.old_owner,
+                new_owner: transfer.new_owner,
+                txn_hash: transfer.txn_hash,
+                block_number,
+                time,
+                held_for_secs: (time - held_since).max(0),
+            };
+            held_since = time;
+            hop
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(ApiResponse::new(AssetDetailResponse {
+        asset_id: asset.asset_id,
+        description: asset.description,
+        owner: asset.owner,
+        owner_held_for_secs: (chrono::Utc::now().timestamp() - held_since).max(0),
+        registration: AssetRegistration {
+            registrant,
+            registered_at: asset.registered_at,
+            txn_hash,
+            block_number,
+        },
+        provenance,
+    })))
 }
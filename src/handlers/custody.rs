@@ -0,0 +1,96 @@
+use crate::address::EvmAddress;
+use crate::auth::ApiKeyIdentity;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiJson, ApiPath};
+use crate::{
+    app_state::AppState,
+    models::{ApiResponse, CreateCustodyUserInput, CustodyUser, CustodyUserResponse},
+    schema::custody_users,
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use std::sync::Arc;
+
+#[utoipa::path(
+    post,
+    path = "/custody/users",
+    request_body(content = CreateCustodyUserInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Custodial user created with a fresh signing key. Only the API key that created it can act as it.", body = ApiResponse<CustodyUserResponse>),
+        (status = 400, description = "Invalid username", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the writer role", body = ErrorResponse),
+        (status = 409, description = "Username already taken", body = ErrorResponse),
+        (status = 503, description = "Custody is not configured", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn create_custody_user(
+    State(state): State<Arc<AppState>>,
+    api_key: ApiKeyIdentity,
+    ApiJson(input): ApiJson<CreateCustodyUserInput>,
+) -> Result<Json<ApiResponse<CustodyUserResponse>>, ApiError> {
+    let username = input.username.trim();
+    if username.is_empty() || !username.is_ascii() {
+        return Err(ApiError::bad_request(format!(
+            "Invalid custody username: {:?}",
+            input.username
+        )));
+    }
+
+    let custody = state
+        .custody
+        .as_ref()
+        .ok_or_else(|| ApiError::unavailable("Custody is not configured"))?;
+    let (address, encrypted_key) = custody.generate()?;
+
+    let conn = &mut state.db_pool.get()?;
+    let user = diesel::insert_into(custody_users::table)
+        .values((
+            custody_users::username.eq(username),
+            custody_users::address.eq(EvmAddress(address)),
+            custody_users::encrypted_key.eq(encrypted_key),
+            custody_users::created_at.eq(chrono::Utc::now().timestamp()),
+            custody_users::api_key_id.eq(api_key.id),
+        ))
+        .returning(CustodyUser::as_returning())
+        .get_result::<CustodyUser>(conn)
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => ApiError::conflict(format!("Custody username already taken: {}", username)),
+            e => ApiError::from(e),
+        })?;
+
+    println!("🔐 Custody user {} created: 0x{:x}", user.username, address);
+
+    Ok(Json(ApiResponse::new(user.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/custody/users/{username}",
+    params(("username" = String, Path, description = "Custodial username")),
+    responses(
+        (status = 200, description = "Custodial user found", body = ApiResponse<CustodyUserResponse>),
+        (status = 404, description = "Unknown user", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_custody_user(
+    ApiPath(username): ApiPath<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<CustodyUserResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let user = custody_users::table
+        .filter(custody_users::username.eq(&username))
+        .select(CustodyUser::as_select())
+        .first::<CustodyUser>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Unknown custody user: {}", username)))?;
+
+    Ok(Json(ApiResponse::new(user.into())))
+}
@@ -0,0 +1,22 @@
+use crate::app_state::AppState;
+use crate::extract::ApiJson;
+use crate::graphql::DbLoader;
+use async_graphql::http::GraphiQLSource;
+use axum::{Json, extract::State, response::Html};
+use std::sync::Arc;
+
+// GraphQL over the indexed assets, transfers and owners. Errors, including invalid ids and
+// addresses, come back in the response's `errors` as GraphQL clients expect, not as an
+// ErrorResponse.
+pub async fn graphql(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let request = request.data(DbLoader::new(state.db_pool.clone()));
+    Json(state.graphql.execute(request).await)
+}
+
+// Interactive GraphQL playground, the counterpart of Swagger UI for /graphql
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
@@ -1,4 +1,14 @@
 pub mod assets;
 pub mod transfer;
 pub mod search;
-pub mod analytics;
\ No newline at end of file
+pub mod analytics;
+pub mod custody;
+pub mod wallet;
+pub mod transactions;
+pub mod asset_audit;
+pub mod owners;
+pub mod stream;
+pub mod webhooks;
+pub mod graphql;
+pub mod api_keys;
+pub mod siwe;
@@ -0,0 +1,141 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiPath;
+use crate::siwe::SignedIn;
+use crate::{
+    address::EvmAddress,
+    app_state::AppState,
+    asset_id::AssetId,
+    event_log::EventKind,
+    models::{ApiResponse, Asset, OwnerLabel, OwnerProfileResponse},
+    schema::{asset_events, assets, custody_users, transfers},
+};
+use axum::{Json, extract::State};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[utoipa::path(
+    get,
+    path = "/owners/{address}",
+    params(("address" = EvmAddress, Path, description = "Owner address")),
+    responses(
+        (status = 200, description = "Assets the address holds, held and registered, with its transfer counts, activity span and labels", body = ApiResponse<OwnerProfileResponse>),
+        (status = 400, description = "Invalid address", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_owner_profile(
+    ApiPath(address): ApiPath<EvmAddress>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<OwnerProfileResponse>>, ApiError> {
+    Ok(Json(ApiResponse::new(owner_profile(&state, address)?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/owners/me",
+    security(("siwe_session" = [])),
+    responses(
+        (status = 200, description = "The owner profile of the signed-in address", body = ApiResponse<OwnerProfileResponse>),
+        (status = 401, description = "Not signed in, or the session is unknown, expired or revoked", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_my_profile(
+    State(state): State<Arc<AppState>>,
+    SignedIn(session): SignedIn,
+) -> Result<Json<ApiResponse<OwnerProfileResponse>>, ApiError> {
+    Ok(Json(ApiResponse::new(owner_profile(
+        &state,
+        session.address,
+    )?)))
+}
+
+fn owner_profile(state: &AppState, address: EvmAddress) -> Result<OwnerProfileResponse, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let held = assets::table
+        .filter(assets::owner.eq(address))
+        .order((assets::registered_at.asc(), assets::asset_id.asc()))
+        .load::<Asset>(conn)?;
+    let held_ids = held
+        .iter()
+        .map(|asset| asset.asset_id)
+        .collect::<HashSet<_>>();
+
+    // An asset can come back to a former owner, so only the ones not held now count as gone
+    let previously_held = transfers::table
+        .filter(transfers::old_owner.eq(address))
+        .select(transfers::asset_id)
+        .distinct()
+        .order(transfers::asset_id.asc())
+        .load::<AssetId>(conn)?
+        .into_iter()
+        .filter(|asset_id| !held_ids.contains(asset_id))
+        .collect::<Vec<_>>();
+
+    let registered = asset_events::table
+        .filter(asset_events::kind.eq(EventKind::Registered))
+        .filter(asset_events::to_address.eq(address))
+        .order((asset_events::timestamp.asc(), asset_events::id.asc()))
+        .select(asset_events::asset_id)
+        .load::<AssetId>(conn)?;
+
+    let transfers_sent = transfers::table
+        .filter(transfers::old_owner.eq(address))
+        .select(count_star())
+        .first::<i64>(conn)?;
+    let transfers_received = transfers::table
+        .filter(transfers::new_owner.eq(address))
+        .select(count_star())
+        .first::<i64>(conn)?;
+
+    let (first_activity, last_activity) = asset_events::table
+        .filter(
+            asset_events::from_address
+                .eq(address)
+                .or(asset_events::to_address.eq(address)),
+        )
+        .select((
+            diesel::dsl::min(asset_events::timestamp),
+            diesel::dsl::max(asset_events::timestamp),
+        ))
+        .first::<(Option<i64>, Option<i64>)>(conn)?;
+
+    let is_custodial = custody_users::table
+        .filter(custody_users::address.eq(address))
+        .select(count_star())
+        .first::<i64>(conn)?
+        > 0;
+
+    let mut labels = Vec::new();
+    if address.0 == state.contract.client().address() {
+        labels.push(OwnerLabel::ServiceWallet);
+    }
+    if is_custodial {
+        labels.push(OwnerLabel::CustodialUser);
+    }
+    if !registered.is_empty() {
+        labels.push(OwnerLabel::Registrant);
+    }
+    if !held.is_empty() {
+        labels.push(OwnerLabel::Holder);
+    } else if !previously_held.is_empty() {
+        labels.push(OwnerLabel::FormerHolder);
+    }
+
+    Ok(OwnerProfileResponse {
+        address,
+        labels,
+        held,
+        previously_held,
+        registered,
+        transfers_sent,
+        transfers_received,
+        first_activity,
+        last_activity,
+    })
+}
@@ -1,6 +1,12 @@
 use crate::app_state::AppState;
-use crate::models::{ApiResponse, Asset, SearchInput};
-use crate::schema::assets;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiJson, ApiQuery};
+use crate::models::{ApiResponse, AssetEvent, AssetSearchHit, EventSearchInput, SearchInput};
+use crate::pagination::{
+    AssetPageParams, EventPageParams, load_asset_page, load_event_page, load_ranked_asset_page,
+};
+use crate::schema::{asset_events, assets};
+use crate::text_search::{matches, parse_query, with_snippets};
 use diesel::ExpressionMethods;
 use diesel::prelude::*;
 use diesel::{QueryDsl, RunQueryDsl};
@@ -10,49 +16,173 @@ use std::sync::Arc;
     post,
     path = "/search",
     request_body(content = SearchInput, content_type = "application/json"),
+    params(AssetPageParams),
     responses(
-        (status = 200, description = "Search results retrieved successfully", body = ApiResponse<Vec<Asset>>),
-        (status = 400, description = "Invalid search parameters"),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "One page of search results, best match first for a `q` search without a `sort`", body = ApiResponse<Vec<AssetSearchHit>>),
+        (status = 400, description = "Invalid search parameters, cursor, limit or sort", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 pub async fn search_events(
     state: axum::extract::State<Arc<AppState>>,
-    axum::Json(input): axum::Json<SearchInput>,
-) -> Result<axum::Json<ApiResponse<Vec<Asset>>>, axum::http::StatusCode> {
-    let conn = &mut state.db_pool.get().map_err(|e| {
-        eprintln!("DB connection error: {:?}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    let mut query = assets::table.into_boxed();
-
-    //anyone you use will be used for the search
-    if let Some(asset_id) = input.asset_id {
-        query = query.filter(assets::asset_id.eq(asset_id));
-    }
+    ApiQuery(page): ApiQuery<AssetPageParams>,
+    ApiJson(input): ApiJson<SearchInput>,
+) -> Result<axum::Json<ApiResponse<Vec<AssetSearchHit>>>, ApiError> {
+    let tsquery = input.q.as_deref().map(parse_query).transpose()?;
 
-    if let Some(owner_address) = input.owner_address {
-        query = query.filter(assets::owner.eq(owner_address));
-    }
+    let conn = &mut state.db_pool.get()?;
 
-    if let Some(start_date) = input.start_date {
-        query = query.filter(assets::registered_at.ge(start_date));
-    }
+    let filtered = || {
+        let mut query = assets::table.into_boxed();
 
-    if let Some(end_date) = input.end_date {
-        query = query.filter(assets::registered_at.le(end_date));
-    }
+        //anyone you use will be used for the search
+        if let Some(asset_id) = &input.asset_id {
+            query = query.filter(assets::asset_id.eq(*asset_id));
+        }
+
+        if let Some(owner_address) = input.owner_address {
+            query = query.filter(assets::owner.eq(owner_address));
+        }
 
-    let results = query.load::<Asset>(conn).map_err(|e| {
-        eprintln!("Search query error: {:?}", e);
-        axum::http::StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+        if let Some(start_date) = input.start_date {
+            query = query.filter(assets::registered_at.ge(start_date));
+        }
 
-    Ok(axum::Json(ApiResponse { data: results }))
+        if let Some(end_date) = input.end_date {
+            query = query.filter(assets::registered_at.le(end_date));
+        }
+
+        if let Some(tsquery) = &tsquery {
+            query = query.filter(matches(tsquery));
+        }
+        query
+    };
+
+    let results = match &tsquery {
+        Some(tsquery) if page.sort.is_none() => {
+            load_ranked_asset_page(conn, filtered, tsquery, &page)?
+        }
+        _ => {
+            let ApiResponse {
+                data,
+                next_cursor,
+                total,
+            } = load_asset_page(conn, filtered, &page)?;
+            let data = match &tsquery {
+                Some(tsquery) => with_snippets(conn, tsquery, data)?,
+                None => data
+                    .into_iter()
+                    .map(|asset| AssetSearchHit {
+                        asset,
+                        rank: None,
+                        snippet: None,
+                    })
+                    .collect(),
+            };
+            ApiResponse {
+                data,
+                next_cursor,
+                total,
+            }
+        }
+    };
+
+    Ok(axum::Json(results))
 }
 
+#[utoipa::path(
+    post,
+    path = "/search/events",
+    request_body(content = EventSearchInput, content_type = "application/json"),
+    params(EventPageParams),
+    responses(
+        (status = 200, description = "One page of registrations and transfers matching every given filter", body = ApiResponse<Vec<AssetEvent>>),
+        (status = 400, description = "Invalid search parameters, cursor, limit or sort", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn search_asset_events(
+    state: axum::extract::State<Arc<AppState>>,
+    ApiQuery(page): ApiQuery<EventPageParams>,
+    ApiJson(input): ApiJson<EventSearchInput>,
+) -> Result<axum::Json<ApiResponse<Vec<AssetEvent>>>, ApiError> {
+    let txn_hash = input
+        .txn_hash
+        .as_deref()
+        .map(parse_txn_hash)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    let conn = &mut state.db_pool.get()?;
+
+    let filtered = || {
+        let mut query = asset_events::table.into_boxed();
+
+        if let Some(kind) = input.kind {
+            query = query.filter(asset_events::kind.eq(kind));
+        }
+
+        if let Some(asset_id) = input.asset_id {
+            query = query.filter(asset_events::asset_id.eq(asset_id));
+        }
+
+        if let Some(from_address) = input.from_address {
+            query = query.filter(asset_events::from_address.eq(from_address));
+        }
+
+        if let Some(to_address) = input.to_address {
+            query = query.filter(asset_events::to_address.eq(to_address));
+        }
+
+        if let Some(address) = input.address {
+            query = query.filter(
+                asset_events::from_address
+                    .eq(address)
+                    .or(asset_events::to_address.eq(address)),
+            );
+        }
+
+        if let Some(start_date) = input.start_date {
+            query = query.filter(asset_events::timestamp.ge(start_date));
+        }
+
+        if let Some(end_date) = input.end_date {
+            query = query.filter(asset_events::timestamp.le(end_date));
+        }
+
+        if let Some(from_block) = input.from_block {
+            query = query.filter(asset_events::block_number.ge(from_block));
+        }
+
+        if let Some(to_block) = input.to_block {
+            query = query.filter(asset_events::block_number.le(to_block));
+        }
+
+        if let Some(txn_hash) = &txn_hash {
+            query = query.filter(asset_events::txn_hash.eq(txn_hash));
+        }
+        query
+    };
+
+    let results = load_event_page(conn, filtered, &page)?;
+
+    Ok(axum::Json(results))
+}
+
+// Transaction hashes are stored as lowercase 0x-prefixed hex
+fn parse_txn_hash(input: &str) -> Result<String, String> {
+    let digits = input.strip_prefix("0x").unwrap_or(input);
+    let bytes = hex::decode(digits).map_err(|e| format!("Invalid txn_hash format: {:?}", e))?;
+    if bytes.len() != 32 {
+        return Err(format!(
+            "Invalid txn_hash length: expected 32 bytes, got {}",
+            bytes.len()
+        ));
+    }
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
 //
 // {
 // "asset_id": "string",
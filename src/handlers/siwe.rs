@@ -0,0 +1,176 @@
+use crate::auth::hash_key;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiJson;
+use crate::siwe::{NONCE_TTL_SECS, SignedIn, SiweMessage, generate_token};
+use crate::{
+    app_state::AppState,
+    models::{ApiResponse, SiweNonceResponse, SiweSessionResponse, SiweVerifyInput},
+    schema::{siwe_nonces, siwe_sessions},
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use ethers::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// The sign-in domain, or 503 when SIWE_DOMAIN is not set
+fn siwe_domain(state: &AppState) -> Result<&str, ApiError> {
+    state
+        .siwe
+        .domain
+        .as_deref()
+        .ok_or_else(|| ApiError::unavailable("Sign-In with Ethereum is not configured"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/nonce",
+    responses(
+        (status = 200, description = "A fresh nonce to put in a Sign-In with Ethereum message. It can be used once, before it expires.", body = ApiResponse<SiweNonceResponse>),
+        (status = 503, description = "Sign-In with Ethereum is not configured", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_siwe_nonce(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<SiweNonceResponse>>, ApiError> {
+    siwe_domain(&state)?;
+    // Nothing is stored until the nonce signs in, so asking for nonces costs no writes
+    let expires_at = chrono::Utc::now().timestamp() + NONCE_TTL_SECS;
+    let nonce = state.siwe.issue_nonce(expires_at);
+
+    Ok(Json(ApiResponse::new(SiweNonceResponse {
+        nonce,
+        expires_at,
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/verify",
+    request_body(content = SiweVerifyInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Signed in. The session token is only returned here; send it as Authorization: Bearer <token>.", body = ApiResponse<SiweSessionResponse>),
+        (status = 400, description = "Malformed message or signature", body = ErrorResponse),
+        (status = 401, description = "Wrong domain or chain, expired or unknown nonce, or the signature is not the address's", body = ErrorResponse),
+        (status = 503, description = "Sign-In with Ethereum is not configured", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn verify_siwe(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<SiweVerifyInput>,
+) -> Result<Json<ApiResponse<SiweSessionResponse>>, ApiError> {
+    let domain = siwe_domain(&state)?;
+    let message = SiweMessage::parse(&input.message).map_err(ApiError::bad_request)?;
+    let signature = Signature::from_str(input.signature.trim())
+        .map_err(|e| ApiError::bad_request(format!("Invalid signature: {}", e)))?;
+
+    let chain_id = state.contract.client().signer().chain_id();
+    let now = chrono::Utc::now();
+    message.verify(&input.message, &signature, domain, chain_id, now)?;
+
+    let now = now.timestamp();
+    let nonce_expires_at = state
+        .siwe
+        .nonce_expiry(&message.nonce)
+        .ok_or_else(|| ApiError::unauthorized("Unknown nonce"))?;
+    if nonce_expires_at <= now {
+        return Err(ApiError::unauthorized("Nonce has expired"));
+    }
+    let mut expires_at = now + state.siwe.session_ttl_secs;
+    if let Some(expiry) = message.expiration_time {
+        expires_at = expires_at.min(expiry.timestamp());
+    }
+    let token = generate_token();
+
+    let conn = &mut state.db_pool.get()?;
+    conn.transaction::<_, ApiError, _>(|conn| {
+        // Spent nonces are kept until they expire, after which nonce_expiry refuses them anyway.
+        // Spending the nonce first means a replayed message can never open a second session.
+        diesel::delete(siwe_nonces::table.filter(siwe_nonces::expires_at.le(now))).execute(conn)?;
+        let spent = diesel::insert_into(siwe_nonces::table)
+            .values((
+                siwe_nonces::nonce.eq(&message.nonce),
+                siwe_nonces::issued_at.eq(nonce_expires_at - NONCE_TTL_SECS),
+                siwe_nonces::expires_at.eq(nonce_expires_at),
+                siwe_nonces::used_at.eq(now),
+            ))
+            .on_conflict(siwe_nonces::nonce)
+            .do_nothing()
+            .execute(conn)?;
+        if spent == 0 {
+            return Err(ApiError::unauthorized("Nonce was already used"));
+        }
+
+        diesel::insert_into(siwe_sessions::table)
+            .values((
+                siwe_sessions::token_hash.eq(hash_key(&token)),
+                siwe_sessions::address.eq(message.address),
+                siwe_sessions::created_at.eq(now),
+                siwe_sessions::expires_at.eq(expires_at),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    Ok(Json(ApiResponse::new(SiweSessionResponse {
+        address: message.address,
+        expires_at,
+        token: Some(token),
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/session",
+    security(("siwe_session" = [])),
+    responses(
+        (status = 200, description = "The signed-in address and when its session expires", body = ApiResponse<SiweSessionResponse>),
+        (status = 401, description = "Not signed in, or the session is unknown, expired or revoked", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_siwe_session(
+    SignedIn(session): SignedIn,
+) -> Result<Json<ApiResponse<SiweSessionResponse>>, ApiError> {
+    Ok(Json(ApiResponse::new(SiweSessionResponse {
+        address: session.address,
+        expires_at: session.expires_at,
+        token: None,
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    security(("siwe_session" = [])),
+    responses(
+        (status = 200, description = "Session revoked; its token no longer signs in", body = ApiResponse<SiweSessionResponse>),
+        (status = 401, description = "Not signed in, or the session is unknown, expired or revoked", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn logout_siwe(
+    State(state): State<Arc<AppState>>,
+    SignedIn(session): SignedIn,
+) -> Result<Json<ApiResponse<SiweSessionResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    diesel::update(
+        siwe_sessions::table
+            .find(session.id)
+            .filter(siwe_sessions::revoked_at.is_null()),
+    )
+    .set(siwe_sessions::revoked_at.eq(chrono::Utc::now().timestamp()))
+    .execute(conn)?;
+
+    Ok(Json(ApiResponse::new(SiweSessionResponse {
+        address: session.address,
+        expires_at: session.expires_at,
+        token: None,
+    })))
+}
@@ -0,0 +1,185 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::event_log::PublishedEvent;
+use crate::extract::ApiQuery;
+use crate::{
+    address::EvmAddress, app_state::AppState, asset_id::AssetId, models::AssetEvent,
+    schema::asset_events,
+};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use diesel::prelude::*;
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+use utoipa::IntoParams;
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+// Events replayed from the log per query while a subscriber catches up
+const BACKLOG_BATCH: i64 = 500;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventStreamParams {
+    /// Only events of this asset
+    pub asset_id: Option<AssetId>,
+    /// Only events this address sent, received or registered
+    pub address: Option<EvmAddress>,
+}
+
+impl EventStreamParams {
+    fn matches(&self, event: &AssetEvent) -> bool {
+        self.asset_id
+            .is_none_or(|asset_id| event.asset_id == asset_id)
+            && self.address.is_none_or(|address| {
+                event.from_address == Some(address) || event.to_address == address
+            })
+    }
+}
+
+// A subscriber's position: events published up to seq last_sent have been delivered. While
+// catching_up, the next events come from the stored log rather than the live channel.
+struct Subscription {
+    state: Arc<AppState>,
+    params: EventStreamParams,
+    receiver: Receiver<PublishedEvent>,
+    last_sent: i64,
+    catching_up: bool,
+    backlog: VecDeque<PublishedEvent>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/events/stream",
+    params(
+        EventStreamParams,
+        ("Last-Event-ID" = Option<i64>, Header, description = "Resume after this message id, replaying what was missed from the event log")
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events stream of registrations and transfers as the indexer commits them. Each message is named after the event kind, its id is the event's position in publish order and its data is the event as JSON.", content_type = "text/event-stream", body = AssetEvent),
+        (status = 400, description = "Invalid filter or Last-Event-ID", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn stream_events(
+    State(state): State<Arc<AppState>>,
+    ApiQuery(params): ApiQuery<EventStreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .ok_or_else(|| ApiError::bad_request("Invalid Last-Event-ID header"))
+        })
+        .transpose()?;
+
+    // Subscribe before reading the log, so nothing committed in between is missed
+    let receiver = state.events.subscribe();
+    let last_sent = match last_event_id {
+        Some(last_event_id) => last_event_id,
+        // A new subscriber starts at the live edge
+        None => {
+            let conn = &mut state.db_pool.get()?;
+            asset_events::table
+                .select(diesel::dsl::max(asset_events::published_seq))
+                .first::<Option<i64>>(conn)?
+                .unwrap_or_default()
+        }
+    };
+
+    let subscription = Subscription {
+        state,
+        params,
+        receiver,
+        last_sent,
+        catching_up: last_event_id.is_some(),
+        backlog: VecDeque::new(),
+    };
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let PublishedEvent { seq, event } = next_event(&mut subscription).await?;
+        subscription.last_sent = subscription.last_sent.max(seq);
+        let message = Event::default()
+            .id(seq.to_string())
+            .event(event.kind.as_str())
+            .json_data(&event);
+        Some((message, subscription))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+// The next event for the subscriber, or None once the stream should end. A subscriber that
+// falls too far behind the live channel catches up from the log; if the log can't be read the
+// stream ends, and the client reconnects with Last-Event-ID.
+async fn next_event(subscription: &mut Subscription) -> Option<PublishedEvent> {
+    loop {
+        if let Some(event) = subscription.backlog.pop_front() {
+            return Some(event);
+        }
+
+        if subscription.catching_up {
+            let batch = match load_after(subscription) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    eprintln!("Failed to replay events: {:?}", e);
+                    return None;
+                }
+            };
+            subscription.catching_up = batch.len() as i64 == BACKLOG_BATCH;
+            subscription.backlog.extend(batch);
+            continue;
+        }
+
+        match subscription.receiver.recv().await {
+            // Events already replayed from the log can still arrive on the channel. Seqs are
+            // handed out as events go live, so anything past last_sent is new.
+            Ok(published) => {
+                if published.seq > subscription.last_sent
+                    && subscription.params.matches(&published.event)
+                {
+                    return Some(published);
+                }
+            }
+            Err(RecvError::Lagged(_)) => subscription.catching_up = true,
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+fn load_after(subscription: &Subscription) -> Result<Vec<PublishedEvent>, ApiError> {
+    let conn = &mut subscription.state.db_pool.get()?;
+
+    let mut query = asset_events::table
+        .filter(asset_events::published_seq.gt(subscription.last_sent))
+        .into_boxed();
+    if let Some(asset_id) = subscription.params.asset_id {
+        query = query.filter(asset_events::asset_id.eq(asset_id));
+    }
+    if let Some(address) = subscription.params.address {
+        query = query.filter(
+            asset_events::from_address
+                .eq(address)
+                .or(asset_events::to_address.eq(address)),
+        );
+    }
+    Ok(query
+        .order(asset_events::published_seq.asc())
+        .limit(BACKLOG_BATCH)
+        .select((
+            asset_events::published_seq.assume_not_null(),
+            AssetEvent::as_select(),
+        ))
+        .load::<(i64, AssetEvent)>(conn)?
+        .into_iter()
+        .map(|(seq, event)| PublishedEvent { seq, event })
+        .collect())
+}
@@ -0,0 +1,40 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::ApiPath;
+use crate::{
+    app_state::AppState,
+    models::{ApiResponse, TransactionRecord},
+    schema::transactions,
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use std::sync::Arc;
+
+#[utoipa::path(
+    get,
+    path = "/transactions/{hash}",
+    params(("hash" = String, Path, description = "Transaction hash")),
+    responses(
+        (status = 200, description = "Receipt details and fees of a recorded transaction", body = ApiResponse<TransactionRecord>),
+        (status = 404, description = "Transaction not recorded", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_transaction(
+    ApiPath(hash): ApiPath<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<TransactionRecord>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+
+    let txn_hash = format!(
+        "0x{}",
+        hash.strip_prefix("0x").unwrap_or(&hash).to_lowercase()
+    );
+    let record = transactions::table
+        .find(&txn_hash)
+        .select(TransactionRecord::as_select())
+        .first::<TransactionRecord>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Transaction not recorded: {}", txn_hash)))?;
+    Ok(Json(ApiResponse::new(record)))
+}
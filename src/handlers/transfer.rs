@@ -1,58 +1,96 @@
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiPath, ApiQuery};
 use crate::models::TransferByDate;
 use crate::{
     app_state::AppState,
-    models::{ApiResponse, Asset, Transfer},
-    schema::{assets, transfers},
-};
-use axum::http::StatusCode;
-use axum::{
-    Json,
-    extract::{Path, State},
+    models::{ApiResponse, Asset, TransactionRecord, TransferWithTransaction},
+    pagination::{AssetPageParams, TransferPageParams, load_asset_page, load_transfer_page},
+    schema::{assets, transactions, transfers},
 };
+use axum::{Json, extract::State};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use utoipa::path;
 
 #[utoipa::path(
     get,
     path = "/transfers/{asset_id}",
-    params(("asset_id" = String, Path, description = "Asset ID")),
+    params(("asset_id" = AssetId, Path, description = "Asset ID"), TransferPageParams),
+    responses(
+        (status = 200, description = "One page of the asset's transfers, each with its transaction receipt once recorded. `next_cursor` is set when there are more pages and `total` counts the asset's transfers across all of them.", body = ApiResponse<Vec<TransferWithTransaction>>),
+        (status = 400, description = "Invalid asset ID, cursor, limit or sort", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
 )]
 pub async fn get_transfers_by_asset(
-    Path(asset_id): Path<String>,
+    ApiPath(asset_id): ApiPath<AssetId>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<ApiResponse<Vec<Transfer>>>, StatusCode> {
-    let conn = &mut state
-        .db_pool
-        .get()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ApiQuery(page): ApiQuery<TransferPageParams>,
+) -> Result<Json<ApiResponse<Vec<TransferWithTransaction>>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
 
-    let results = transfers::table
-        .filter(transfers::asset_id.eq(asset_id))
-        .load::<Transfer>(conn)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(ApiResponse { data: results }))
+    let page = load_transfer_page(
+        conn,
+        || {
+            transfers::table
+                .filter(transfers::asset_id.eq(asset_id))
+                .into_boxed()
+        },
+        &page,
+    )?;
+
+    // Transfers indexed before receipts were recorded have no transaction yet
+    let hashes: Vec<&String> = page
+        .data
+        .iter()
+        .map(|transfer| &transfer.txn_hash)
+        .collect();
+    let records: HashMap<String, TransactionRecord> = transactions::table
+        .filter(transactions::txn_hash.eq_any(hashes))
+        .select(TransactionRecord::as_select())
+        .load::<TransactionRecord>(conn)?
+        .into_iter()
+        .map(|record| (record.txn_hash.clone(), record))
+        .collect();
+    Ok(Json(page.map(|transfers| {
+        transfers
+            .into_iter()
+            .map(|transfer| TransferWithTransaction {
+                transaction: records.get(&transfer.txn_hash).cloned(),
+                transfer,
+            })
+            .collect()
+    })))
 }
 
 #[utoipa::path(
     get,
     path = "/assets/owner/{address}",
-    params(("address" = String, Path, description = "Owner address")),
+    params(("address" = EvmAddress, Path, description = "Owner address"), AssetPageParams),
+    responses(
+        (status = 200, description = "One page of the assets the address owns. `next_cursor` is set when there are more pages and `total` counts the address's assets across all of them.", body = ApiResponse<Vec<Asset>>),
+        (status = 400, description = "Invalid address, cursor, limit or sort", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
 )]
 pub async fn get_assets_by_owner(
-    Path(address): Path<String>,
+    ApiPath(address): ApiPath<EvmAddress>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<ApiResponse<Vec<Asset>>>, StatusCode> {
-    let conn = &mut state
-        .db_pool
-        .get()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let results = assets::table
-        .filter(assets::owner.eq(address))
-        .load::<Asset>(conn)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(ApiResponse { data: results }))
+    ApiQuery(page): ApiQuery<AssetPageParams>,
+) -> Result<Json<ApiResponse<Vec<Asset>>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let results = load_asset_page(
+        conn,
+        || assets::table.filter(assets::owner.eq(address)).into_boxed(),
+        &page,
+    )?;
+    Ok(Json(results))
 }
 
 #[utoipa::path(
@@ -60,17 +98,14 @@ pub async fn get_assets_by_owner(
     path = "/transfers_by_date",
     responses(
         (status = 200, description = "Transfer counts by date retrieved successfully", body = Vec<TransferByDate>),
-        (status = 500, description = "Internal server error")
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "SwitchAssets"
 )]
 pub async fn get_transfers_by_date(
     state: State<Arc<AppState>>,
-) -> Result<Json<Vec<TransferByDate>>, StatusCode> {
-    let conn = &mut state.db_pool.get().map_err(|e| {
-        eprintln!("DB connection error: {:?}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+) -> Result<Json<Vec<TransferByDate>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
 
     let results = transfers::table
         .select((
@@ -85,11 +120,7 @@ pub async fn get_transfers_by_date(
         .order(diesel::dsl::sql::<diesel::sql_types::Timestamp>(
             "date_trunc('day', to_timestamp(timestamp))",
         ))
-        .load::<(NaiveDateTime, i64)>(conn)
-        .map_err(|e| {
-            eprintln!("Transfers by date query error: {:?}", e);
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .load::<(NaiveDateTime, i64)>(conn)?;
 
     let transfer_data = results
         .into_iter()
@@ -0,0 +1,86 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::{
+    app_state::AppState,
+    models::{ApiResponse, WalletBalanceResponse},
+};
+use axum::http::header;
+use axum::{Json, extract::State, response::IntoResponse};
+use std::fmt::Write;
+use std::sync::Arc;
+
+#[utoipa::path(
+    get,
+    path = "/wallet/balance",
+    responses(
+        (status = 200, description = "Latest service wallet balance and remaining write capacity", body = ApiResponse<WalletBalanceResponse>),
+        (status = 503, description = "The balance monitor has not completed a poll yet", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_wallet_balance(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<WalletBalanceResponse>>, ApiError> {
+    let reading = state
+        .balance
+        .latest()
+        .await
+        .ok_or_else(|| ApiError::unavailable("No wallet balance reading yet"))?;
+    Ok(Json(ApiResponse::new(reading)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Service wallet gauges in Prometheus text format", body = String, content_type = "text/plain")
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut body = String::new();
+    if let Some(reading) = state.balance.latest().await {
+        let gauges = [
+            (
+                "switch_wallet_balance_wei",
+                "Service wallet balance in wei",
+                reading.balance_wei.clone(),
+            ),
+            (
+                "switch_wallet_gas_price_wei",
+                "Gas price at the last poll in wei",
+                reading.gas_price_wei.clone(),
+            ),
+            (
+                "switch_wallet_registers_remaining",
+                "Asset registrations the balance can still pay for",
+                reading.registers_remaining.to_string(),
+            ),
+            (
+                "switch_wallet_transfers_remaining",
+                "Asset transfers the balance can still pay for",
+                reading.transfers_remaining.to_string(),
+            ),
+            (
+                "switch_wallet_low_funds",
+                "1 when remaining capacity is below the alert threshold",
+                (reading.low_funds as u8).to_string(),
+            ),
+            (
+                "switch_wallet_checked_at_seconds",
+                "Unix time of the last successful poll",
+                reading.checked_at.to_string(),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(body, "# HELP {} {}", name, help);
+            let _ = writeln!(body, "# TYPE {} gauge", name);
+            let _ = writeln!(
+                body,
+                "{}{{address=\"{}\"}} {}",
+                name, reading.address, value
+            );
+        }
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
@@ -0,0 +1,302 @@
+use crate::error::{ApiError, ErrorResponse};
+use crate::extract::{ApiJson, ApiPath, ApiQuery};
+use crate::webhooks::{STATUS_DELIVERED, STATUS_FAILED, STATUS_PENDING, generate_secret};
+use crate::{
+    app_state::AppState,
+    models::{ApiResponse, WebhookDelivery, WebhookInput, WebhookResponse, WebhookSubscription},
+    schema::{webhook_deliveries, webhook_subscriptions},
+};
+use axum::{Json, extract::State};
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+const MAX_DELIVERY_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryListParams {
+    /// Only deliveries in this status: pending, delivered or failed
+    pub status: Option<String>,
+    /// Newest deliveries to return, default 50, at most 500
+    pub limit: Option<i64>,
+}
+
+// Rejects anything but an absolute http(s) url, and an empty secret
+fn validate_input(input: &WebhookInput) -> Result<(), ApiError> {
+    let url = reqwest::Url::parse(&input.url)
+        .map_err(|e| ApiError::bad_request(format!("Invalid webhook url: {}", e)))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(ApiError::bad_request("Webhook url must be http or https"));
+    }
+    if input
+        .secret
+        .as_deref()
+        .is_some_and(|secret| secret.is_empty())
+    {
+        return Err(ApiError::bad_request("Webhook secret must not be empty"));
+    }
+    Ok(())
+}
+
+fn load_subscription(conn: &mut PgConnection, id: i32) -> Result<WebhookSubscription, ApiError> {
+    webhook_subscriptions::table
+        .find(id)
+        .select(WebhookSubscription::as_select())
+        .first::<WebhookSubscription>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Webhook {} not found", id)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    request_body(content = WebhookInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Subscription created. The signing secret is only returned here.", body = ApiResponse<WebhookResponse>),
+        (status = 400, description = "Invalid url, secret or filter", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<WebhookInput>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, ApiError> {
+    validate_input(&input)?;
+    let secret = input.secret.unwrap_or_else(generate_secret);
+
+    let conn = &mut state.db_pool.get()?;
+    let subscription = diesel::insert_into(webhook_subscriptions::table)
+        .values((
+            webhook_subscriptions::url.eq(&input.url),
+            webhook_subscriptions::secret.eq(&secret),
+            webhook_subscriptions::event_kind.eq(input.event_kind),
+            webhook_subscriptions::asset_id.eq(input.asset_id),
+            webhook_subscriptions::address.eq(input.address),
+            webhook_subscriptions::active.eq(input.active.unwrap_or(true)),
+            webhook_subscriptions::created_at.eq(chrono::Utc::now().timestamp()),
+        ))
+        .returning(WebhookSubscription::as_returning())
+        .get_result::<WebhookSubscription>(conn)?;
+
+    Ok(Json(ApiResponse::new(WebhookResponse {
+        secret: Some(secret),
+        ..subscription.into()
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    responses(
+        (status = 200, description = "All webhook subscriptions", body = ApiResponse<Vec<WebhookResponse>>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<Vec<WebhookResponse>>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let subscriptions = webhook_subscriptions::table
+        .order(webhook_subscriptions::id.asc())
+        .select(WebhookSubscription::as_select())
+        .load::<WebhookSubscription>(conn)?;
+
+    Ok(Json(ApiResponse::new(
+        subscriptions.into_iter().map(Into::into).collect(),
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    params(("id" = i32, Path, description = "Webhook subscription id")),
+    responses(
+        (status = 200, description = "The webhook subscription", body = ApiResponse<WebhookResponse>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 404, description = "No such webhook", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn get_webhook(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let subscription = load_subscription(conn, id)?;
+    Ok(Json(ApiResponse::new(subscription.into())))
+}
+
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    params(("id" = i32, Path, description = "Webhook subscription id")),
+    request_body(content = WebhookInput, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Url and filters replaced. The secret and active flag are kept unless given.", body = ApiResponse<WebhookResponse>),
+        (status = 400, description = "Invalid url, secret or filter", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 404, description = "No such webhook", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn update_webhook(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+    ApiJson(input): ApiJson<WebhookInput>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, ApiError> {
+    validate_input(&input)?;
+
+    let conn = &mut state.db_pool.get()?;
+    let current = load_subscription(conn, id)?;
+    let subscription = diesel::update(webhook_subscriptions::table.find(id))
+        .set((
+            webhook_subscriptions::url.eq(&input.url),
+            webhook_subscriptions::secret.eq(input.secret.as_ref().unwrap_or(&current.secret)),
+            webhook_subscriptions::event_kind.eq(input.event_kind),
+            webhook_subscriptions::asset_id.eq(input.asset_id),
+            webhook_subscriptions::address.eq(input.address),
+            webhook_subscriptions::active.eq(input.active.unwrap_or(current.active)),
+        ))
+        .returning(WebhookSubscription::as_returning())
+        .get_result::<WebhookSubscription>(conn)?;
+
+    Ok(Json(ApiResponse::new(subscription.into())))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    params(("id" = i32, Path, description = "Webhook subscription id")),
+    responses(
+        (status = 200, description = "Subscription and its delivery log deleted", body = ApiResponse<WebhookResponse>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 404, description = "No such webhook", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn delete_webhook(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let subscription = diesel::delete(webhook_subscriptions::table.find(id))
+        .returning(WebhookSubscription::as_returning())
+        .get_result::<WebhookSubscription>(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::not_found(format!("Webhook {} not found", id)))?;
+
+    Ok(Json(ApiResponse::new(subscription.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    params(
+        ("id" = i32, Path, description = "Webhook subscription id"),
+        DeliveryListParams
+    ),
+    responses(
+        (status = 200, description = "Delivery log of the subscription, newest first", body = ApiResponse<Vec<WebhookDelivery>>),
+        (status = 400, description = "Invalid status or limit", body = ErrorResponse),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 404, description = "No such webhook", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn list_webhook_deliveries(
+    ApiPath(id): ApiPath<i32>,
+    State(state): State<Arc<AppState>>,
+    ApiQuery(params): ApiQuery<DeliveryListParams>,
+) -> Result<Json<ApiResponse<Vec<WebhookDelivery>>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT);
+    if !(1..=MAX_DELIVERY_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit {} outside of 1..={}",
+            limit, MAX_DELIVERY_LIMIT
+        )));
+    }
+
+    let conn = &mut state.db_pool.get()?;
+    load_subscription(conn, id)?;
+
+    let mut query = webhook_deliveries::table
+        .filter(webhook_deliveries::subscription_id.eq(id))
+        .into_boxed();
+    if let Some(status) = params.status {
+        if ![STATUS_PENDING, STATUS_DELIVERED, STATUS_FAILED].contains(&status.as_str()) {
+            return Err(ApiError::bad_request(format!(
+                "Unknown delivery status: {}",
+                status
+            )));
+        }
+        query = query.filter(webhook_deliveries::status.eq(status));
+    }
+    let deliveries = query
+        .order(webhook_deliveries::id.desc())
+        .limit(limit)
+        .select(WebhookDelivery::as_select())
+        .load::<WebhookDelivery>(conn)?;
+
+    Ok(Json(ApiResponse::new(deliveries)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+    params(
+        ("id" = i32, Path, description = "Webhook subscription id"),
+        ("delivery_id" = i32, Path, description = "Delivery to send again")
+    ),
+    responses(
+        (status = 200, description = "Delivery queued to be sent on the next poll, with a fresh retry budget", body = ApiResponse<WebhookDelivery>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorResponse),
+        (status = 403, description = "API key lacks the admin role", body = ErrorResponse),
+        (status = 404, description = "No such delivery for this webhook", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "SwitchAssets"
+)]
+pub async fn redeliver_webhook(
+    ApiPath((id, delivery_id)): ApiPath<(i32, i32)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<WebhookDelivery>>, ApiError> {
+    let conn = &mut state.db_pool.get()?;
+    let delivery = diesel::update(
+        webhook_deliveries::table
+            .find(delivery_id)
+            .filter(webhook_deliveries::subscription_id.eq(id)),
+    )
+    .set((
+        webhook_deliveries::status.eq(STATUS_PENDING),
+        webhook_deliveries::attempts.eq(0),
+        webhook_deliveries::next_attempt_at.eq(chrono::Utc::now().timestamp()),
+    ))
+    .returning(WebhookDelivery::as_returning())
+    .get_result::<WebhookDelivery>(conn)
+    .optional()?
+    .ok_or_else(|| {
+        ApiError::not_found(format!(
+            "Delivery {} of webhook {} not found",
+            delivery_id, id
+        ))
+    })?;
+
+    Ok(Json(ApiResponse::new(delivery)))
+}
@@ -0,0 +1,93 @@
+use crate::error::ApiError;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+// Lets a client retry a write without it being submitted twice. The key names the job the
+// write is tracked as, so a retry resumes (or simply reports) the original transaction.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+// The Idempotency-Key header, if the request sent one
+pub struct IdempotencyKey(pub Option<String>);
+
+impl IdempotencyKey {
+    // Batch endpoints also take the key in the body; either works, but they must agree
+    pub fn or_body(self, body_key: &str) -> Result<String, ApiError> {
+        let body_key = body_key.trim();
+        match self.0 {
+            Some(header_key) if !body_key.is_empty() && header_key != body_key => {
+                Err(ApiError::bad_request(format!(
+                    "Idempotency-Key header {} does not match body idempotency_key {}",
+                    header_key, body_key
+                )))
+            }
+            Some(header_key) => Ok(header_key),
+            None if body_key.is_empty() => Err(ApiError::bad_request(
+                "Batch request without an idempotency key",
+            )),
+            None => Ok(body_key.to_string()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for IdempotencyKey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(header) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(IdempotencyKey(None));
+        };
+        let key = header.to_str().map(str::trim).unwrap_or_default();
+        if key.is_empty() {
+            return Err(ApiError::bad_request(format!(
+                "Invalid {} header",
+                IDEMPOTENCY_KEY_HEADER
+            )));
+        }
+        Ok(IdempotencyKey(Some(key.to_string())))
+    }
+}
+
+// Keys with a request currently working on them, per client like the jobs they name. A second
+// request with the same key while the first is still sending would otherwise see queued items
+// and submit them again.
+#[derive(Clone, Default)]
+pub struct InFlightKeys(Arc<Mutex<HashSet<(String, String)>>>);
+
+impl InFlightKeys {
+    pub fn claim(&self, client: &str, key: &str) -> Result<InFlightGuard, ApiError> {
+        let mut keys = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let claimed = (client.to_string(), key.to_string());
+        if !keys.insert(claimed.clone()) {
+            return Err(ApiError::conflict(format!(
+                "Request with idempotency key {} is already in progress",
+                key
+            )));
+        }
+        Ok(InFlightGuard {
+            keys: self.clone(),
+            key: claimed,
+        })
+    }
+}
+
+// Releases the key when the request finishes, including when the client goes away mid-request
+pub struct InFlightGuard {
+    keys: InFlightKeys,
+    key: (String, String),
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut keys = self
+            .keys
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        keys.remove(&self.key);
+    }
+}
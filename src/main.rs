@@ -1,16 +1,37 @@
+mod address;
 mod app_route;
 mod app_state;
+mod asset_id;
+mod auth;
+mod balance_monitor;
 mod contract_calls;
+mod custody;
+mod error;
+mod event_log;
 mod events;
+mod extract;
+mod graphql;
 mod handlers;
+mod idempotency;
 mod models;
+mod pagination;
 mod schema;
+mod signer;
+mod siwe;
+mod spending;
 mod swagger;
+mod text_search;
+mod transactions;
+mod wei;
+mod webhooks;
 
+use crate::balance_monitor::monitor_balance;
 use crate::events::listen_for_events;
+use crate::webhooks::deliver_webhooks;
 
 use crate::app_route::app_router;
 use crate::app_state::AppState;
+use crate::auth::bootstrap_admin_key;
 use crate::handlers::analytics::generate_analytics;
 use diesel::prelude::*;
 use diesel::prelude::*;
@@ -28,6 +49,7 @@ use utoipa::OpenApi;
 #[tokio::main]
 async fn main() -> Result<()> {
     let state = Arc::from(AppState::init().await?);
+    bootstrap_admin_key(&state)?;
 
     // spawn event listener in background
     let state_clone = state.clone();
@@ -37,6 +59,20 @@ async fn main() -> Result<()> {
         }
     });
 
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = monitor_balance(state_clone).await {
+            eprintln!("Error in balance monitor: {:?}", e);
+        }
+    });
+
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = deliver_webhooks(state_clone).await {
+            eprintln!("Error in webhook delivery: {:?}", e);
+        }
+    });
+
     // 2-factor auth... lol
     if let Err(e) = generate_analytics(&state).await {
         eprintln!("Analytics generation error: {:?}", e);
@@ -49,6 +85,7 @@ async fn main() -> Result<()> {
 
     println!("Server running on {:?}", addr);
     println!("Swagger UI available at http://127.0.0.1:8080/swagger-ui/index.html#/");
+    println!("GraphiQL available at http://127.0.0.1:8080/graphiql");
 
     axum::serve(listener, app).await?;
 
@@ -1,24 +1,29 @@
-use diesel::{AsChangeset, Insertable, Queryable};
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::auth::Role;
+use crate::event_log::EventKind;
+use crate::wei::Wei;
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use ethabi::ethereum_types::{H160, H256};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(Queryable, Insertable, AsChangeset, Serialize, ToSchema)]
+#[derive(Queryable, Insertable, AsChangeset, Serialize, Clone, ToSchema)]
 #[diesel(table_name = crate::schema::assets)]
 pub struct Asset {
-    pub asset_id: String,
-    pub owner: String,
+    pub asset_id: AssetId,
+    pub owner: EvmAddress,
     pub description: String,
     pub registered_at: i64,
 }
 
-#[derive(Queryable, Insertable, AsChangeset, Serialize, ToSchema)]
+#[derive(Queryable, Selectable, Insertable, AsChangeset, Serialize, Clone, ToSchema)]
 #[diesel(table_name = crate::schema::transfers)]
 pub struct Transfer {
     pub id: i32,
-    pub asset_id: String,
-    pub old_owner: String,
-    pub new_owner: String,
+    pub asset_id: AssetId,
+    pub old_owner: EvmAddress,
+    pub new_owner: EvmAddress,
     pub timestamp: i64,
     pub txn_hash: String,
 }
@@ -26,6 +31,39 @@ pub struct Transfer {
 #[derive(Serialize, ToSchema)]
 pub struct ApiResponse<T> {
     pub data: T,
+    /// Set on list endpoints when there are more pages: pass it back as `cursor` to fetch the next one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Set on list endpoints: number of rows matching the request across all pages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn new(data: T) -> Self {
+        ApiResponse {
+            data,
+            next_cursor: None,
+            total: None,
+        }
+    }
+
+    pub fn page(data: T, next_cursor: Option<String>, total: i64) -> Self {
+        ApiResponse {
+            data,
+            next_cursor,
+            total: Some(total),
+        }
+    }
+
+    // Converts the data of a page, keeping its cursor and total
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ApiResponse<U> {
+        ApiResponse {
+            data: f(self.data),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -35,38 +73,38 @@ pub struct RegisterAssetInput {
 
 #[derive(Deserialize, ToSchema)]
 pub struct TransferAssetInput {
-    pub asset_id: String,
-    pub new_owner: String,
+    pub asset_id: AssetId,
+    pub new_owner: EvmAddress,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct OwnershipTransferredResponse {
-    pub asset_id: String,
-    pub old_owner: String,
-    pub new_owner: String,
+    pub asset_id: AssetId,
+    pub old_owner: EvmAddress,
+    pub new_owner: EvmAddress,
 }
 
 impl OwnershipTransferredResponse {
     pub fn init() -> Self {
         Self {
-            asset_id: format!("0x{}", hex::encode(H256::zero())),
-            old_owner: format!("0x{}", hex::encode(H160::zero())),
-            new_owner: format!("0x{}", hex::encode(H160::zero())),
+            asset_id: AssetId(H256::zero()),
+            old_owner: EvmAddress(H160::zero()),
+            new_owner: EvmAddress(H160::zero()),
         }
     }
 
     pub fn new(asset_id: H256, old_owner: H160, new_owner: H160) -> Self {
         Self {
-            asset_id: format!("0x{}", hex::encode(asset_id)),
-            old_owner: format!("0x{}", hex::encode(old_owner.0)),
-            new_owner: format!("0x{}", hex::encode(new_owner.0)),
+            asset_id: AssetId(asset_id),
+            old_owner: EvmAddress(old_owner),
+            new_owner: EvmAddress(new_owner),
         }
     }
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct GetAssetInput {
-    pub asset_id: String,
+    pub asset_id: AssetId,
 }
 
 #[derive(Clone, Debug)]
@@ -93,10 +131,58 @@ impl AssetRegisteredResponse {
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SearchInput {
-    pub asset_id: Option<String>,
-    pub owner_address: Option<String>,
+    // Full-text query over descriptions: every word must match, `word*` matches as a prefix
+    pub q: Option<String>,
+    pub asset_id: Option<AssetId>,
+    pub owner_address: Option<EvmAddress>,
+    pub start_date: Option<i64>,
+    pub end_date: Option<i64>,
+}
+
+// An asset found by /search. rank and snippet are set when the search has a `q`; the snippet
+// is HTML-escaped and matched words in it are wrapped in <mark>.
+#[derive(Serialize, ToSchema)]
+pub struct AssetSearchHit {
+    #[serde(flatten)]
+    pub asset: Asset,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+// Filters of an event search, all optional and combined with AND. Dates are unix seconds and
+// ranges are inclusive.
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub struct EventSearchInput {
+    pub kind: Option<EventKind>,
+    pub asset_id: Option<AssetId>,
+    // Sender of a transfer; registrations have none
+    pub from_address: Option<EvmAddress>,
+    // Receiver of a transfer, or registrant of a registration
+    pub to_address: Option<EvmAddress>,
+    // Either side of the event
+    pub address: Option<EvmAddress>,
     pub start_date: Option<i64>,
     pub end_date: Option<i64>,
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    pub txn_hash: Option<String>,
+}
+
+// A registration or transfer from the event feed. block_number and txn_hash are unknown for
+// registrations indexed before the feed existed.
+#[derive(Queryable, Selectable, Serialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::asset_events)]
+pub struct AssetEvent {
+    pub id: i32,
+    pub kind: EventKind,
+    pub asset_id: AssetId,
+    pub from_address: Option<EvmAddress>,
+    pub to_address: EvmAddress,
+    pub timestamp: i64,
+    pub block_number: Option<i64>,
+    pub txn_hash: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -117,3 +203,479 @@ pub struct Analytics {
     pub total_transfers: i64,
     pub top_owners: Vec<TopOwner>,
 }
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::batch_jobs)]
+pub struct BatchJob {
+    pub id: i32,
+    pub idempotency_key: String,
+    pub kind: String,
+    pub created_at: i64,
+    pub signer: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::batch_job_items)]
+pub struct BatchJobItem {
+    pub id: i32,
+    pub item_index: i32,
+    pub description: Option<String>,
+    pub asset_id: Option<String>,
+    pub status: String,
+    pub txn_hash: Option<String>,
+    pub error: Option<String>,
+    pub new_owner: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterBatchInput {
+    #[serde(default)]
+    pub idempotency_key: String,
+    pub descriptions: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TransferBatchInput {
+    #[serde(default)]
+    pub idempotency_key: String,
+    pub transfers: Vec<TransferAssetInput>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchItemResult {
+    pub index: i32,
+    pub description: Option<String>,
+    pub asset_id: Option<String>,
+    pub new_owner: Option<String>,
+    pub status: String,
+    pub txn_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+impl From<BatchJobItem> for BatchItemResult {
+    fn from(item: BatchJobItem) -> Self {
+        Self {
+            index: item.item_index,
+            description: item.description,
+            asset_id: item.asset_id,
+            new_owner: item.new_owner,
+            status: item.status,
+            txn_hash: item.txn_hash,
+            error: item.error,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchJobResponse {
+    pub idempotency_key: String,
+    pub kind: String,
+    pub created_at: i64,
+    pub signer: Option<String>,
+    pub items: Vec<BatchItemResult>,
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::custody_users)]
+pub struct CustodyUser {
+    pub username: String,
+    pub address: EvmAddress,
+    pub encrypted_key: String,
+    pub created_at: i64,
+    pub api_key_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateCustodyUserInput {
+    pub username: String,
+}
+
+// Public view of a custodial user, the encrypted key never leaves the server
+#[derive(Serialize, ToSchema)]
+pub struct CustodyUserResponse {
+    pub username: String,
+    pub address: EvmAddress,
+    pub created_at: i64,
+}
+
+impl From<CustodyUser> for CustodyUserResponse {
+    fn from(user: CustodyUser) -> Self {
+        Self {
+            username: user.username,
+            address: user.address,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BuildRegisterInput {
+    pub from: EvmAddress,
+    pub description: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BuildTransferInput {
+    pub from: EvmAddress,
+    pub asset_id: AssetId,
+    pub new_owner: EvmAddress,
+}
+
+// EIP-1559 transaction request for the caller's own wallet to sign, quantities are
+// hex-encoded the way eth_sendTransaction expects them
+#[derive(Serialize, ToSchema)]
+pub struct UnsignedTransactionResponse {
+    pub from: EvmAddress,
+    pub to: EvmAddress,
+    pub data: String,
+    pub value: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub gas: String,
+    pub max_fee_per_gas: String,
+    pub max_priority_fee_per_gas: String,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RelayInput {
+    pub raw_transaction: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RelayResponse {
+    pub txn_hash: String,
+    pub from: EvmAddress,
+    pub job: BatchJobResponse,
+}
+
+// Latest reading of the service wallet taken by the balance monitor. Wei amounts are decimal
+// strings since they can exceed what JSON numbers hold exactly.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct WalletBalanceResponse {
+    pub address: EvmAddress,
+    pub balance_wei: String,
+    pub gas_price_wei: String,
+    pub register_cost_wei: String,
+    pub transfer_cost_wei: String,
+    pub registers_remaining: u64,
+    pub transfers_remaining: u64,
+    pub low_funds: bool,
+    pub checked_at: i64,
+}
+
+// A mined transaction with its fees, recorded for API-sent writes and indexed events alike.
+// total_fee_wei is the L2 execution fee plus, on OP-stack chains like Base, the L1 data fee.
+// Wei amounts are decimal strings, as they can exceed what a JSON number holds exactly.
+#[derive(Queryable, Selectable, Serialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::transactions)]
+pub struct TransactionRecord {
+    pub txn_hash: String,
+    pub from_address: EvmAddress,
+    pub to_address: Option<EvmAddress>,
+    pub nonce: Option<i64>,
+    pub block_number: Option<i64>,
+    pub block_timestamp: Option<i64>,
+    pub status: String,
+    pub gas_used: Option<i64>,
+    pub effective_gas_price_wei: Option<Wei>,
+    pub l1_fee_wei: Option<Wei>,
+    pub total_fee_wei: Option<Wei>,
+    pub sent_by_api: bool,
+    pub recorded_at: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TransferWithTransaction {
+    #[serde(flatten)]
+    pub transfer: Transfer,
+    pub transaction: Option<TransactionRecord>,
+}
+
+// Where and when an asset was registered. txn_hash and block_number are unknown for assets
+// indexed before registrations were recorded as events.
+#[derive(Serialize, ToSchema)]
+pub struct AssetRegistration {
+    pub registrant: EvmAddress,
+    // Block time of the registration, as stored by the contract
+    pub registered_at: i64,
+    pub txn_hash: Option<String>,
+    pub block_number: Option<i64>,
+}
+
+// One transfer in an asset's chain of custody. time is the block time when the transaction's
+// block is known, otherwise the time the transfer was indexed.
+#[derive(Serialize, ToSchema)]
+pub struct ProvenanceHop {
+    pub old_owner: EvmAddress,
+    pub new_owner: EvmAddress,
+    pub txn_hash: String,
+    pub block_number: Option<i64>,
+    pub time: i64,
+    // How long old_owner held the asset before this transfer
+    pub held_for_secs: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AssetDetailResponse {
+    pub asset_id: AssetId,
+    pub description: String,
+    pub owner: EvmAddress,
+    // How long the current owner has held the asset so far
+    pub owner_held_for_secs: i64,
+    pub registration: AssetRegistration,
+    // Oldest transfer first
+    pub provenance: Vec<ProvenanceHop>,
+}
+
+// What an address is, as far as this service can tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerLabel {
+    // The wallet this service signs with
+    ServiceWallet,
+    // A wallet the service holds the key of for a custodial user
+    CustodialUser,
+    // Registered at least one asset
+    Registrant,
+    // Holds at least one asset now
+    Holder,
+    // Held assets once but holds none now
+    FormerHolder,
+}
+
+// An address's activity across the indexed assets, transfers and registrations. Activity
+// times span its registrations and transfers in either direction.
+#[derive(Serialize, ToSchema)]
+pub struct OwnerProfileResponse {
+    pub address: EvmAddress,
+    pub labels: Vec<OwnerLabel>,
+    pub held: Vec<Asset>,
+    // Held at some point and since transferred away
+    pub previously_held: Vec<AssetId>,
+    pub registered: Vec<AssetId>,
+    pub transfers_sent: i64,
+    pub transfers_received: i64,
+    pub first_activity: Option<i64>,
+    pub last_activity: Option<i64>,
+}
+
+// What the contract says an address holds right now, and where the index disagrees
+#[derive(Serialize, ToSchema)]
+pub struct OwnerHoldingsResponse {
+    pub address: EvmAddress,
+    pub assets: Vec<Asset>,
+    // Held on-chain but not indexed under this owner
+    pub missing_from_index: Vec<AssetId>,
+    // Indexed under this owner but no longer held on-chain
+    pub stale_in_index: Vec<AssetId>,
+    pub in_sync: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeriveAssetIdInput {
+    pub owner: EvmAddress,
+    pub registered_at: u64,
+    pub description: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DerivedAssetIdResponse {
+    pub asset_id: AssetId,
+}
+
+// An assets row read as stored, without validating its id or owner, so that corrupt rows
+// can still be inspected
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::assets)]
+pub struct RawAsset {
+    pub asset_id: String,
+    pub owner: String,
+    pub description: String,
+    pub registered_at: i64,
+}
+
+// Whether a stored asset's id is consistent with its registration data. derived_asset_id is
+// the id the data actually produces, for comparing against the stored one.
+#[derive(Serialize, ToSchema)]
+pub struct AssetVerification {
+    pub asset_id: String,
+    pub owner: String,
+    pub registrant: Option<String>,
+    pub derived_asset_id: Option<String>,
+    pub valid: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AssetAuditResponse {
+    pub checked: usize,
+    pub inconsistent: usize,
+    pub mismatches: Vec<AssetVerification>,
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::webhook_subscriptions)]
+pub struct WebhookSubscription {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event_kind: Option<EventKind>,
+    pub asset_id: Option<AssetId>,
+    pub address: Option<EvmAddress>,
+    pub active: bool,
+    pub created_at: i64,
+}
+
+// Creates a subscription, or replaces one's url and filters. Filters left out match every
+// event. A secret left out is generated on create and kept on update.
+#[derive(Deserialize, ToSchema)]
+pub struct WebhookInput {
+    pub url: String,
+    pub secret: Option<String>,
+    pub event_kind: Option<EventKind>,
+    pub asset_id: Option<AssetId>,
+    /// Matches events this address sent, received or registered
+    pub address: Option<EvmAddress>,
+    pub active: Option<bool>,
+}
+
+// A subscription as the API shows it. The secret is only returned when it is set.
+#[derive(Serialize, ToSchema)]
+pub struct WebhookResponse {
+    pub id: i32,
+    pub url: String,
+    pub event_kind: Option<EventKind>,
+    pub asset_id: Option<AssetId>,
+    pub address: Option<EvmAddress>,
+    pub active: bool,
+    pub created_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<WebhookSubscription> for WebhookResponse {
+    fn from(subscription: WebhookSubscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url,
+            event_kind: subscription.event_kind,
+            asset_id: subscription.asset_id,
+            address: subscription.address,
+            active: subscription.active,
+            created_at: subscription.created_at,
+            secret: None,
+        }
+    }
+}
+
+// One event owed to one subscription. status is pending until the endpoint answers 2xx
+// (delivered) or the attempts run out (failed).
+#[derive(Queryable, Selectable, Serialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::webhook_deliveries)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub subscription_id: i32,
+    pub event_id: i32,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: i64,
+    pub last_attempt_at: Option<i64>,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+// The JSON body POSTed to a webhook endpoint
+#[derive(Serialize, ToSchema)]
+pub struct WebhookPayload {
+    pub delivery_id: i32,
+    pub subscription_id: i32,
+    pub event: AssetEvent,
+}
+
+// The hash stays in the database: keys are only ever shown once, when created
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::api_keys)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub role: Role,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyInput {
+    pub name: String,
+    pub role: Role,
+}
+
+// An API key as the admin endpoints show it. key is only set in the response that created it.
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub role: Role,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            key_prefix: api_key.key_prefix,
+            role: api_key.role,
+            created_at: api_key.created_at,
+            last_used_at: api_key.last_used_at,
+            revoked_at: api_key.revoked_at,
+            key: None,
+        }
+    }
+}
+
+// A request an API key made to a write or admin endpoint, with the status it got
+#[derive(Queryable, Selectable, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::api_key_writes)]
+pub struct ApiKeyWrite {
+    pub id: i32,
+    pub api_key_id: i32,
+    pub method: String,
+    pub path: String,
+    pub status: i32,
+    pub request_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SiweNonceResponse {
+    pub nonce: String,
+    pub expires_at: i64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SiweVerifyInput {
+    /// The EIP-4361 message, exactly as the wallet signed it
+    pub message: String,
+    /// 65-byte personal_sign signature of the message, hex encoded
+    pub signature: String,
+}
+
+// A Sign-In with Ethereum session. token is only set in the response that created it.
+#[derive(Serialize, ToSchema)]
+pub struct SiweSessionResponse {
+    pub address: EvmAddress,
+    pub expires_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
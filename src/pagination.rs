@@ -0,0 +1,545 @@
+use crate::asset_id::AssetId;
+use crate::error::ApiError;
+use crate::models::{ApiResponse, Asset, AssetEvent, AssetSearchHit, Transfer};
+use crate::schema::{asset_events, assets, transfers};
+use crate::text_search::{headline, rank};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+pub const MAX_PAGE_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetSort {
+    #[default]
+    RegisteredAt,
+    AssetId,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferSort {
+    #[default]
+    Timestamp,
+    Id,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSort {
+    #[default]
+    Timestamp,
+    Id,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AssetPageParams {
+    /// `next_cursor` from the previous page; omit for the first page
+    pub cursor: Option<String>,
+    /// Page size, 1 to 500 (default 50)
+    pub limit: Option<i64>,
+    /// Sort field (default registered_at)
+    pub sort: Option<AssetSort>,
+    /// Sort direction (default asc)
+    pub direction: Option<SortDirection>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransferPageParams {
+    /// `next_cursor` from the previous page; omit for the first page
+    pub cursor: Option<String>,
+    /// Page size, 1 to 500 (default 50)
+    pub limit: Option<i64>,
+    /// Sort field (default timestamp)
+    pub sort: Option<TransferSort>,
+    /// Sort direction (default asc)
+    pub direction: Option<SortDirection>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventPageParams {
+    /// `next_cursor` from the previous page; omit for the first page
+    pub cursor: Option<String>,
+    /// Page size, 1 to 500 (default 50)
+    pub limit: Option<i64>,
+    /// Sort field (default timestamp)
+    pub sort: Option<EventSort>,
+    /// Sort direction (default asc)
+    pub direction: Option<SortDirection>,
+}
+
+// The cursor is opaque to clients: the sort it was issued for plus the last row's sort key,
+// so the next page starts strictly after that row even if rows were inserted meanwhile
+#[derive(Serialize, Deserialize)]
+struct Cursor<S, K> {
+    sort: S,
+    direction: SortDirection,
+    key: K,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AssetKey {
+    registered_at: i64,
+    asset_id: AssetId,
+}
+
+// Full-text results are ranked best match first unless another sort is asked for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RankSort {
+    Relevance,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RankKey {
+    rank: f32,
+    asset_id: AssetId,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransferKey {
+    timestamp: i64,
+    id: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EventKey {
+    timestamp: i64,
+    id: i32,
+}
+
+fn page_limit(limit: Option<i64>) -> Result<i64, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_LIMIT
+        )));
+    }
+    Ok(limit)
+}
+
+fn encode_cursor<S: Serialize, K: Serialize>(cursor: &Cursor<S, K>) -> Result<String, ApiError> {
+    serde_json::to_vec(cursor)
+        .map(hex::encode)
+        .map_err(|e| ApiError::internal(format!("Cursor encoding error: {:?}", e)))
+}
+
+// Decodes a client cursor, rejecting one issued for a different sort or direction
+fn decode_cursor<S: DeserializeOwned + PartialEq, K: DeserializeOwned>(
+    cursor: Option<&str>,
+    sort: &S,
+    direction: SortDirection,
+) -> Result<Option<K>, ApiError> {
+    let Some(cursor) = cursor else {
+        return Ok(None);
+    };
+    let decoded = hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Cursor<S, K>>(&bytes).ok())
+        .ok_or_else(|| ApiError::bad_request("Invalid page cursor"))?;
+    if decoded.sort != *sort || decoded.direction != direction {
+        return Err(ApiError::bad_request(
+            "Page cursor was issued for a different sort or direction",
+        ));
+    }
+    Ok(Some(decoded.key))
+}
+
+// What a client asked of one list: where to resume, how many rows, and in which order
+struct PageRequest<'p, S> {
+    cursor: Option<&'p str>,
+    limit: Option<i64>,
+    sort: S,
+    direction: SortDirection,
+}
+
+// Keyset pagination shared by every list. Checks the limit and cursor, counts the total, and
+// has `load` fetch the page in order, starting strictly after the cursor's key. One row more
+// than the page is asked for, which tells us whether there is a next page; its cursor carries
+// the `key` of the page's last row.
+fn load_page<S, K, T>(
+    conn: &mut PgConnection,
+    request: PageRequest<'_, S>,
+    total: impl FnOnce(&mut PgConnection) -> QueryResult<i64>,
+    load: impl FnOnce(&mut PgConnection, Option<K>, i64) -> QueryResult<Vec<T>>,
+    key: impl Fn(&T) -> K,
+) -> Result<ApiResponse<Vec<T>>, ApiError>
+where
+    S: Serialize + DeserializeOwned + PartialEq,
+    K: Serialize + DeserializeOwned,
+{
+    let limit = page_limit(request.limit)?;
+    let after: Option<K> = decode_cursor(request.cursor, &request.sort, request.direction)?;
+
+    let total = total(conn)?;
+
+    let mut rows = load(conn, after, limit + 1)?;
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        Some(encode_cursor(&Cursor {
+            sort: request.sort,
+            direction: request.direction,
+            key: key(&rows[rows.len() - 1]),
+        })?)
+    } else {
+        None
+    };
+    Ok(ApiResponse::page(rows, next_cursor, total))
+}
+
+// Loads one page of the assets matched by `filtered`, which is called once for the total and
+// once for the page itself
+pub fn load_asset_page<'a>(
+    conn: &mut PgConnection,
+    filtered: impl Fn() -> assets::BoxedQuery<'a, Pg>,
+    params: &AssetPageParams,
+) -> Result<ApiResponse<Vec<Asset>>, ApiError> {
+    let sort = params.sort.unwrap_or_default();
+    let direction = params.direction.unwrap_or_default();
+    load_page(
+        conn,
+        PageRequest {
+            cursor: params.cursor.as_deref(),
+            limit: params.limit,
+            sort,
+            direction,
+        },
+        |conn| filtered().count().get_result(conn),
+        |conn, after: Option<AssetKey>, limit| {
+            // asset_id is unique, so it breaks ties in registered_at
+            let mut query = filtered();
+            query = match (sort, direction) {
+                (AssetSort::RegisteredAt, SortDirection::Asc) => {
+                    query.order((assets::registered_at.asc(), assets::asset_id.asc()))
+                }
+                (AssetSort::RegisteredAt, SortDirection::Desc) => {
+                    query.order((assets::registered_at.desc(), assets::asset_id.desc()))
+                }
+                (AssetSort::AssetId, SortDirection::Asc) => query.order(assets::asset_id.asc()),
+                (AssetSort::AssetId, SortDirection::Desc) => query.order(assets::asset_id.desc()),
+            };
+            if let Some(key) = after {
+                query = match (sort, direction) {
+                    (AssetSort::RegisteredAt, SortDirection::Asc) => query.filter(
+                        assets::registered_at
+                            .gt(key.registered_at)
+                            .or(assets::registered_at
+                                .eq(key.registered_at)
+                                .and(assets::asset_id.gt(key.asset_id))),
+                    ),
+                    (AssetSort::RegisteredAt, SortDirection::Desc) => query.filter(
+                        assets::registered_at
+                            .lt(key.registered_at)
+                            .or(assets::registered_at
+                                .eq(key.registered_at)
+                                .and(assets::asset_id.lt(key.asset_id))),
+                    ),
+                    (AssetSort::AssetId, SortDirection::Asc) => {
+                        query.filter(assets::asset_id.gt(key.asset_id))
+                    }
+                    (AssetSort::AssetId, SortDirection::Desc) => {
+                        query.filter(assets::asset_id.lt(key.asset_id))
+                    }
+                };
+            }
+            query.limit(limit).load::<Asset>(conn)
+        },
+        |asset| AssetKey {
+            registered_at: asset.registered_at,
+            asset_id: asset.asset_id,
+        },
+    )
+}
+
+// Loads one page of the assets matched by `filtered`, best match for `tsquery` first. asset_id
+// breaks ties in rank, and the direction is ignored: the least relevant match first is never
+// what a search wants.
+pub fn load_ranked_asset_page<'a>(
+    conn: &mut PgConnection,
+    filtered: impl Fn() -> assets::BoxedQuery<'a, Pg>,
+    tsquery: &str,
+    params: &AssetPageParams,
+) -> Result<ApiResponse<Vec<AssetSearchHit>>, ApiError> {
+    let page = load_page(
+        conn,
+        PageRequest {
+            cursor: params.cursor.as_deref(),
+            limit: params.limit,
+            sort: RankSort::Relevance,
+            direction: params.direction.unwrap_or_default(),
+        },
+        |conn| filtered().count().get_result(conn),
+        |conn, after: Option<RankKey>, limit| {
+            let mut query = filtered().order((rank(tsquery).desc(), assets::asset_id.asc()));
+            if let Some(key) = after {
+                query = query.filter(
+                    rank(tsquery).lt(key.rank).or(rank(tsquery)
+                        .eq(key.rank)
+                        .and(assets::asset_id.gt(key.asset_id))),
+                );
+            }
+            query
+                .select((assets::all_columns, rank(tsquery), headline(tsquery)))
+                .limit(limit)
+                .load::<(Asset, f32, String)>(conn)
+        },
+        |(asset, rank, _)| RankKey {
+            rank: *rank,
+            asset_id: asset.asset_id,
+        },
+    )?;
+    Ok(page.map(|rows| {
+        rows.into_iter()
+            .map(|(asset, rank, snippet)| AssetSearchHit {
+                asset,
+                rank: Some(rank),
+                snippet: Some(snippet),
+            })
+            .collect()
+    }))
+}
+
+// Loads one page of the transfers matched by `filtered`, in the same way as load_asset_page
+pub fn load_transfer_page<'a>(
+    conn: &mut PgConnection,
+    filtered: impl Fn() -> transfers::BoxedQuery<'a, Pg>,
+    params: &TransferPageParams,
+) -> Result<ApiResponse<Vec<Transfer>>, ApiError> {
+    let sort = params.sort.unwrap_or_default();
+    let direction = params.direction.unwrap_or_default();
+    load_page(
+        conn,
+        PageRequest {
+            cursor: params.cursor.as_deref(),
+            limit: params.limit,
+            sort,
+            direction,
+        },
+        |conn| filtered().count().get_result(conn),
+        |conn, after: Option<TransferKey>, limit| {
+            // id is the primary key, so it breaks ties in timestamp
+            let mut query = filtered();
+            query = match (sort, direction) {
+                (TransferSort::Timestamp, SortDirection::Asc) => {
+                    query.order((transfers::timestamp.asc(), transfers::id.asc()))
+                }
+                (TransferSort::Timestamp, SortDirection::Desc) => {
+                    query.order((transfers::timestamp.desc(), transfers::id.desc()))
+                }
+                (TransferSort::Id, SortDirection::Asc) => query.order(transfers::id.asc()),
+                (TransferSort::Id, SortDirection::Desc) => query.order(transfers::id.desc()),
+            };
+            if let Some(key) = after {
+                query = match (sort, direction) {
+                    (TransferSort::Timestamp, SortDirection::Asc) => query.filter(
+                        transfers::timestamp
+                            .gt(key.timestamp)
+                            .or(transfers::timestamp
+                                .eq(key.timestamp)
+                                .and(transfers::id.gt(key.id))),
+                    ),
+                    (TransferSort::Timestamp, SortDirection::Desc) => query.filter(
+                        transfers::timestamp
+                            .lt(key.timestamp)
+                            .or(transfers::timestamp
+                                .eq(key.timestamp)
+                                .and(transfers::id.lt(key.id))),
+                    ),
+                    (TransferSort::Id, SortDirection::Asc) => {
+                        query.filter(transfers::id.gt(key.id))
+                    }
+                    (TransferSort::Id, SortDirection::Desc) => {
+                        query.filter(transfers::id.lt(key.id))
+                    }
+                };
+            }
+            query.limit(limit).load::<Transfer>(conn)
+        },
+        |transfer| TransferKey {
+            timestamp: transfer.timestamp,
+            id: transfer.id,
+        },
+    )
+}
+
+// Loads one page of the events matched by `filtered`, in the same way as load_asset_page
+pub fn load_event_page<'a>(
+    conn: &mut PgConnection,
+    filtered: impl Fn() -> asset_events::BoxedQuery<'a, Pg>,
+    params: &EventPageParams,
+) -> Result<ApiResponse<Vec<AssetEvent>>, ApiError> {
+    let sort = params.sort.unwrap_or_default();
+    let direction = params.direction.unwrap_or_default();
+    load_page(
+        conn,
+        PageRequest {
+            cursor: params.cursor.as_deref(),
+            limit: params.limit,
+            sort,
+            direction,
+        },
+        |conn| filtered().count().get_result(conn),
+        |conn, after: Option<EventKey>, limit| {
+            // id is the primary key, so it breaks ties in timestamp
+            let mut query = filtered();
+            query = match (sort, direction) {
+                (EventSort::Timestamp, SortDirection::Asc) => {
+                    query.order((asset_events::timestamp.asc(), asset_events::id.asc()))
+                }
+                (EventSort::Timestamp, SortDirection::Desc) => {
+                    query.order((asset_events::timestamp.desc(), asset_events::id.desc()))
+                }
+                (EventSort::Id, SortDirection::Asc) => query.order(asset_events::id.asc()),
+                (EventSort::Id, SortDirection::Desc) => query.order(asset_events::id.desc()),
+            };
+            if let Some(key) = after {
+                query = match (sort, direction) {
+                    (EventSort::Timestamp, SortDirection::Asc) => query.filter(
+                        asset_events::timestamp
+                            .gt(key.timestamp)
+                            .or(asset_events::timestamp
+                                .eq(key.timestamp)
+                                .and(asset_events::id.gt(key.id))),
+                    ),
+                    (EventSort::Timestamp, SortDirection::Desc) => query.filter(
+                        asset_events::timestamp
+                            .lt(key.timestamp)
+                            .or(asset_events::timestamp
+                                .eq(key.timestamp)
+                                .and(asset_events::id.lt(key.id))),
+                    ),
+                    (EventSort::Id, SortDirection::Asc) => {
+                        query.filter(asset_events::id.gt(key.id))
+                    }
+                    (EventSort::Id, SortDirection::Desc) => {
+                        query.filter(asset_events::id.lt(key.id))
+                    }
+                };
+            }
+            query
+                .limit(limit)
+                .select(AssetEvent::as_select())
+                .load::<AssetEvent>(conn)
+        },
+        |event| EventKey {
+            timestamp: event.timestamp,
+            id: event.id,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset_cursor(sort: AssetSort, direction: SortDirection) -> String {
+        encode_cursor(&Cursor {
+            sort,
+            direction,
+            key: AssetKey {
+                registered_at: 1755547554,
+                asset_id: AssetId::parse("asset_id", &"ab".repeat(32)).unwrap(),
+            },
+        })
+        .unwrap()
+    }
+
+    fn bad_request_message<T>(result: Result<T, ApiError>) -> String {
+        match result {
+            Err(ApiError::BadRequest(message)) => message,
+            Err(e) => panic!("expected a bad request, got {:?}", e),
+            Ok(_) => panic!("expected a bad request, got a key"),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips_its_key() {
+        let cursor = asset_cursor(AssetSort::RegisteredAt, SortDirection::Desc);
+        let key: AssetKey =
+            decode_cursor(Some(&cursor), &AssetSort::RegisteredAt, SortDirection::Desc)
+                .unwrap()
+                .unwrap();
+        assert_eq!(key.registered_at, 1755547554);
+        assert_eq!(key.asset_id.to_string(), format!("0x{}", "ab".repeat(32)));
+
+        let first_page: Option<AssetKey> =
+            decode_cursor(None, &AssetSort::RegisteredAt, SortDirection::Asc).unwrap();
+        assert!(first_page.is_none());
+    }
+
+    #[test]
+    fn cursor_reused_with_another_sort_or_direction_is_rejected() {
+        let cursor = asset_cursor(AssetSort::RegisteredAt, SortDirection::Asc);
+        for (sort, direction) in [
+            (AssetSort::AssetId, SortDirection::Asc),
+            (AssetSort::RegisteredAt, SortDirection::Desc),
+        ] {
+            let message = bad_request_message(decode_cursor::<_, AssetKey>(
+                Some(&cursor),
+                &sort,
+                direction,
+            ));
+            assert_eq!(
+                message,
+                "Page cursor was issued for a different sort or direction"
+            );
+        }
+        // A search cursor can't be used on a plain list either
+        let message = bad_request_message(decode_cursor::<_, AssetKey>(
+            Some(&cursor),
+            &RankSort::Relevance,
+            SortDirection::Asc,
+        ));
+        assert_eq!(message, "Invalid page cursor");
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        let transfer_cursor = encode_cursor(&Cursor {
+            sort: TransferSort::Timestamp,
+            direction: SortDirection::Asc,
+            key: TransferKey {
+                timestamp: 1,
+                id: 1,
+            },
+        })
+        .unwrap();
+        for cursor in [
+            "not hex".to_string(),
+            hex::encode("{\"sort\":"),
+            transfer_cursor,
+        ] {
+            let message = bad_request_message(decode_cursor::<_, AssetKey>(
+                Some(&cursor),
+                &AssetSort::RegisteredAt,
+                SortDirection::Asc,
+            ));
+            assert_eq!(message, "Invalid page cursor");
+        }
+    }
+
+    #[test]
+    fn page_limit_is_bounded() {
+        assert_eq!(page_limit(None).unwrap(), DEFAULT_PAGE_LIMIT);
+        assert_eq!(page_limit(Some(MAX_PAGE_LIMIT)).unwrap(), MAX_PAGE_LIMIT);
+        for limit in [0, -1, MAX_PAGE_LIMIT + 1] {
+            bad_request_message(page_limit(Some(limit)));
+        }
+    }
+}
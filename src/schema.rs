@@ -1,5 +1,51 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_client_quotas (client) {
+        client -> Text,
+        max_day_wei -> Numeric,
+    }
+}
+
+diesel::table! {
+    api_key_writes (id) {
+        id -> Int4,
+        api_key_id -> Int4,
+        method -> Text,
+        path -> Text,
+        status -> Int4,
+        request_id -> Nullable<Text>,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        name -> Text,
+        key_prefix -> Text,
+        key_hash -> Text,
+        role -> Text,
+        created_at -> Int8,
+        last_used_at -> Nullable<Int8>,
+        revoked_at -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    asset_events (id) {
+        id -> Int4,
+        kind -> Text,
+        asset_id -> Text,
+        from_address -> Nullable<Text>,
+        to_address -> Text,
+        timestamp -> Int8,
+        block_number -> Nullable<Int8>,
+        txn_hash -> Nullable<Text>,
+        published_seq -> Nullable<Int8>,
+    }
+}
+
 diesel::table! {
     assets (asset_id) {
         asset_id -> Text,
@@ -9,6 +55,92 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    batch_job_items (id) {
+        id -> Int4,
+        job_id -> Int4,
+        item_index -> Int4,
+        description -> Nullable<Text>,
+        asset_id -> Nullable<Text>,
+        status -> Text,
+        txn_hash -> Nullable<Text>,
+        error -> Nullable<Text>,
+        new_owner -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    batch_jobs (id) {
+        id -> Int4,
+        idempotency_key -> Text,
+        kind -> Text,
+        created_at -> Int8,
+        signer -> Nullable<Text>,
+        client -> Text,
+    }
+}
+
+diesel::table! {
+    custody_users (id) {
+        id -> Int4,
+        username -> Text,
+        address -> Text,
+        encrypted_key -> Text,
+        created_at -> Int8,
+        api_key_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    gas_spend (id) {
+        id -> Int4,
+        txn_hash -> Nullable<Text>,
+        wallet -> Text,
+        client -> Text,
+        cost_wei -> Numeric,
+        settled -> Bool,
+        spent_at -> Int8,
+    }
+}
+
+diesel::table! {
+    siwe_nonces (nonce) {
+        nonce -> Text,
+        issued_at -> Int8,
+        expires_at -> Int8,
+        used_at -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    siwe_sessions (id) {
+        id -> Int4,
+        token_hash -> Text,
+        address -> Text,
+        created_at -> Int8,
+        expires_at -> Int8,
+        revoked_at -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    transactions (txn_hash) {
+        txn_hash -> Text,
+        from_address -> Text,
+        to_address -> Nullable<Text>,
+        nonce -> Nullable<Int8>,
+        block_number -> Nullable<Int8>,
+        status -> Text,
+        gas_used -> Nullable<Int8>,
+        effective_gas_price_wei -> Nullable<Numeric>,
+        l1_fee_wei -> Nullable<Numeric>,
+        total_fee_wei -> Nullable<Numeric>,
+        sent_by_api -> Bool,
+        recorded_at -> Int8,
+        block_timestamp -> Nullable<Int8>,
+    }
+}
+
 diesel::table! {
     transfers (id) {
         id -> Int4,
@@ -20,6 +152,57 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Int4,
+        subscription_id -> Int4,
+        event_id -> Int4,
+        status -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Int8,
+        last_attempt_at -> Nullable<Int8>,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Int8,
+        delivered_at -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    webhook_subscriptions (id) {
+        id -> Int4,
+        url -> Text,
+        secret -> Text,
+        event_kind -> Nullable<Text>,
+        asset_id -> Nullable<Text>,
+        address -> Nullable<Text>,
+        active -> Bool,
+        created_at -> Int8,
+    }
+}
+
+diesel::joinable!(api_key_writes -> api_keys (api_key_id));
+diesel::joinable!(batch_job_items -> batch_jobs (job_id));
+diesel::joinable!(custody_users -> api_keys (api_key_id));
 diesel::joinable!(transfers -> assets (asset_id));
+diesel::joinable!(transfers -> transactions (txn_hash));
+diesel::joinable!(webhook_deliveries -> asset_events (event_id));
+diesel::joinable!(webhook_deliveries -> webhook_subscriptions (subscription_id));
 
-diesel::allow_tables_to_appear_in_same_query!(assets, transfers,);
+diesel::allow_tables_to_appear_in_same_query!(
+    api_client_quotas,
+    api_key_writes,
+    api_keys,
+    asset_events,
+    assets,
+    batch_job_items,
+    batch_jobs,
+    custody_users,
+    gas_spend,
+    siwe_nonces,
+    siwe_sessions,
+    transactions,
+    transfers,
+    webhook_deliveries,
+    webhook_subscriptions,
+);
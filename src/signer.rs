@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+use ethers::core::types::transaction::eip2718::TypedTransaction;
+use ethers::core::types::transaction::eip712::Eip712;
+use ethers::core::utils::hash_message;
+use ethers::prelude::*;
+use ethers::signers::to_eip155_v;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+
+// The key that signs on behalf of the service, chosen by SIGNER_BACKEND:
+// - "keystore": encrypted V3 JSON keystore at KEYSTORE_PATH, password read from
+//   KEYSTORE_PASSWORD_FILE or prompted for on the terminal
+// - "remote": a separate signing process at REMOTE_SIGNER_URL, the key never enters this process
+// - "local": raw PRIVATE_KEY from the environment, for local development only
+#[derive(Debug, Clone)]
+pub enum AppSigner {
+    Local(LocalWallet),
+    Remote(RemoteSigner),
+}
+
+impl AppSigner {
+    pub async fn from_env(chain_id: u64) -> Result<AppSigner> {
+        let backend = env::var("SIGNER_BACKEND").unwrap_or_else(|_| "local".to_string());
+        let signer = match backend.as_str() {
+            "keystore" => {
+                let path = env::var("KEYSTORE_PATH")?;
+                let password = match env::var("KEYSTORE_PASSWORD_FILE") {
+                    Ok(password_file) => std::fs::read_to_string(&password_file)
+                        .map_err(|e| eyre::eyre!("Failed to read {}: {}", password_file, e))?
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string(),
+                    Err(_) => rpassword::prompt_password(format!("Password for {}: ", path))?,
+                };
+                let wallet = LocalWallet::decrypt_keystore(&path, password)
+                    .map_err(|e| eyre::eyre!("Failed to decrypt keystore {}: {}", path, e))?;
+                AppSigner::Local(wallet)
+            }
+            "remote" => {
+                let url = env::var("REMOTE_SIGNER_URL")?;
+                AppSigner::Remote(RemoteSigner::connect(&url).await?)
+            }
+            "local" => {
+                eprintln!(
+                    "⚠️  Signing with a plaintext PRIVATE_KEY, use SIGNER_BACKEND=keystore or remote outside development"
+                );
+                AppSigner::Local(env::var("PRIVATE_KEY")?.parse::<LocalWallet>()?)
+            }
+            other => return Err(eyre::eyre!("Unknown SIGNER_BACKEND: {}", other)),
+        };
+
+        println!("Signer backend: {}", backend);
+        Ok(signer.with_chain_id(chain_id))
+    }
+}
+
+#[async_trait]
+impl Signer for AppSigner {
+    type Error = AppSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            AppSigner::Local(wallet) => Ok(wallet.sign_message(message).await?),
+            AppSigner::Remote(remote) => remote.sign_hash(hash_message(message)).await,
+        }
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            AppSigner::Local(wallet) => Ok(wallet.sign_transaction(tx).await?),
+            AppSigner::Remote(remote) => {
+                // Same EIP-155 handling as Wallet::sign_transaction_sync
+                let chain_id = tx
+                    .chain_id()
+                    .map(|id| id.as_u64())
+                    .unwrap_or(remote.chain_id);
+                let mut tx = tx.clone();
+                tx.set_chain_id(chain_id);
+
+                let mut signature = remote.sign_hash(tx.sighash()).await?;
+                signature.v = to_eip155_v(signature.v as u8 - 27, chain_id);
+                Ok(signature)
+            }
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            AppSigner::Local(wallet) => Ok(wallet.sign_typed_data(payload).await?),
+            AppSigner::Remote(remote) => {
+                let digest = payload
+                    .encode_eip712()
+                    .map_err(|e| AppSignerError::Remote(e.to_string()))?;
+                remote.sign_hash(H256::from(digest)).await
+            }
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            AppSigner::Local(wallet) => wallet.address(),
+            AppSigner::Remote(remote) => remote.address,
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            AppSigner::Local(wallet) => wallet.chain_id(),
+            AppSigner::Remote(remote) => remote.chain_id,
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            AppSigner::Local(wallet) => AppSigner::Local(wallet.with_chain_id(chain_id)),
+            AppSigner::Remote(remote) => AppSigner::Remote(RemoteSigner {
+                chain_id: chain_id.into(),
+                ..remote
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AppSignerError {
+    Wallet(WalletError),
+    Remote(String),
+}
+
+impl fmt::Display for AppSignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppSignerError::Wallet(e) => write!(f, "{}", e),
+            AppSignerError::Remote(e) => write!(f, "Remote signer error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppSignerError {}
+
+impl From<WalletError> for AppSignerError {
+    fn from(e: WalletError) -> Self {
+        AppSignerError::Wallet(e)
+    }
+}
+
+// Talks to a signing process on a local socket over plain HTTP. It only ever sees 32-byte
+// digests, so it needs no knowledge of transactions or the contract:
+//   GET  /address -> { "address": "0x..." }
+//   POST /sign    { "hash": "0x..." } -> { "signature": "0x<r><s><v>" }
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    url: String,
+    http: reqwest::Client,
+    address: Address,
+    chain_id: u64,
+}
+
+#[derive(Deserialize)]
+struct RemoteAddress {
+    address: Address,
+}
+
+#[derive(Serialize)]
+struct RemoteSignRequest {
+    hash: H256,
+}
+
+#[derive(Deserialize)]
+struct RemoteSignResponse {
+    signature: Bytes,
+}
+
+impl RemoteSigner {
+    pub async fn connect(url: &str) -> Result<RemoteSigner> {
+        let url = url.trim_end_matches('/').to_string();
+        let http = reqwest::Client::new();
+        let RemoteAddress { address } = http
+            .get(format!("{}/address", url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(RemoteSigner {
+            url,
+            http,
+            address,
+            chain_id: 1,
+        })
+    }
+
+    // Returns the signature with v normalised to 27/28, as Wallet::sign_hash does
+    async fn sign_hash(&self, hash: H256) -> Result<Signature, AppSignerError> {
+        let response: RemoteSignResponse = self
+            .http
+            .post(format!("{}/sign", self.url))
+            .json(&RemoteSignRequest { hash })
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppSignerError::Remote(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| AppSignerError::Remote(e.to_string()))?;
+
+        checked_signature(hash, response.signature.as_ref(), self.address)
+    }
+}
+
+// Parses a signature over `hash` from the remote signer, which may encode v as 0/1, 27/28 or
+// EIP-155 (chain_id * 2 + 35/36), into the 27/28 form, and refuses one that was not made by
+// `expected`, so the middleware is never handed a signature from some other key
+fn checked_signature(
+    hash: H256,
+    raw: &[u8],
+    expected: Address,
+) -> Result<Signature, AppSignerError> {
+    let mut signature =
+        Signature::try_from(raw).map_err(|e| AppSignerError::Remote(e.to_string()))?;
+    signature.v = match signature.v {
+        0 | 1 => signature.v + 27,
+        27 | 28 => signature.v,
+        v if v >= 35 => 27 + (v - 35) % 2,
+        v => {
+            return Err(AppSignerError::Remote(format!(
+                "signature has invalid v {}",
+                v
+            )));
+        }
+    };
+
+    let recovered = signature
+        .recover(RecoveryMessage::Hash(hash))
+        .map_err(|e| AppSignerError::Remote(e.to_string()))?;
+    if recovered != expected {
+        return Err(AppSignerError::Remote(format!(
+            "signature recovers to 0x{:x}, expected 0x{:x}",
+            recovered, expected
+        )));
+    }
+
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first Hardhat/Anvil development key
+    const KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn signed(hash: H256) -> (LocalWallet, Signature) {
+        let wallet = KEY.parse::<LocalWallet>().unwrap();
+        let signature = wallet.sign_hash(hash).unwrap();
+        (wallet, signature)
+    }
+
+    #[test]
+    fn v_is_normalised_to_27_or_28() {
+        let hash = H256::from(ethers::utils::keccak256("remote signer test"));
+        let (wallet, signature) = signed(hash);
+        assert!(signature.v == 27 || signature.v == 28);
+
+        let parity = signature.v - 27;
+        for v in [signature.v, parity, to_eip155_v(parity as u8, 84532)] {
+            let raw = Signature { v, ..signature }.to_vec();
+            let checked = checked_signature(hash, &raw, wallet.address()).unwrap();
+            assert_eq!(checked, signature, "v {}", v);
+        }
+    }
+
+    #[test]
+    fn invalid_v_is_rejected() {
+        let hash = H256::from(ethers::utils::keccak256("remote signer test"));
+        let (wallet, signature) = signed(hash);
+        let raw = Signature { v: 30, ..signature }.to_vec();
+        assert!(checked_signature(hash, &raw, wallet.address()).is_err());
+    }
+
+    #[test]
+    fn signature_from_another_key_is_rejected() {
+        let hash = H256::from(ethers::utils::keccak256("remote signer test"));
+        let (wallet, signature) = signed(hash);
+        let other = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let error = checked_signature(hash, &signature.to_vec(), other.address()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Remote signer error: signature recovers to 0x{:x}, expected 0x{:x}",
+                wallet.address(),
+                other.address()
+            )
+        );
+
+        // Signed over a different hash, it recovers to some unrelated address
+        let other_hash = H256::from(ethers::utils::keccak256("something else"));
+        assert!(checked_signature(other_hash, &signature.to_vec(), wallet.address()).is_err());
+    }
+}
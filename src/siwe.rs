@@ -0,0 +1,534 @@
+use crate::address::EvmAddress;
+use crate::app_state::AppState;
+use crate::auth::hash_key;
+use crate::error::ApiError;
+use crate::schema::siwe_sessions;
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use ethers::types::Signature;
+use eyre::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::convert::Infallible;
+use std::env;
+use std::sync::Arc;
+
+// Every session token starts with this, like API keys start with swk_
+const TOKEN_PREFIX: &str = "sws_";
+// How long an issued nonce can be signed in with
+pub const NONCE_TTL_SECS: i64 = 600;
+// Bytes of HMAC-SHA256 kept in a nonce
+const NONCE_MAC_LEN: usize = 16;
+
+const PREAMBLE: &str = " wants you to sign in with your Ethereum account:";
+
+// Sign-In with Ethereum (EIP-4361). Configured from the environment:
+// - SIWE_DOMAIN: the domain (host, with port if any) messages must be issued for. Sign-in is
+//   disabled when unset.
+// - SIWE_SESSION_TTL_SECS: how long a session lasts at most, default 86400. A message with an
+//   earlier Expiration Time ends the session then.
+// - SIWE_NONCE_SECRET: key for the MAC that makes nonces unforgeable. Every instance behind the
+//   same load balancer needs the same one. When unset a random key is used, so nonces only
+//   sign in on the instance that issued them, and not after a restart.
+#[derive(Clone, Default)]
+pub struct SiweConfig {
+    pub domain: Option<String>,
+    pub session_ttl_secs: i64,
+    nonce_key: Vec<u8>,
+}
+
+impl SiweConfig {
+    pub fn from_env() -> Result<SiweConfig> {
+        let domain = env::var("SIWE_DOMAIN")
+            .ok()
+            .map(|domain| domain.trim().to_string())
+            .filter(|domain| !domain.is_empty());
+        let session_ttl_secs = match env::var("SIWE_SESSION_TTL_SECS") {
+            Ok(value) => value
+                .parse::<i64>()
+                .map_err(|e| eyre::eyre!("Invalid SIWE_SESSION_TTL_SECS: {}", e))?,
+            Err(_) => 86400,
+        };
+
+        let nonce_key = match env::var("SIWE_NONCE_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                if domain.is_some() {
+                    println!(
+                        "SIWE_NONCE_SECRET not set, sign-in nonces only work on this instance"
+                    );
+                }
+                rand::random::<[u8; 32]>().to_vec()
+            }
+        };
+
+        Ok(SiweConfig {
+            domain,
+            session_ttl_secs: session_ttl_secs.max(1),
+            nonce_key,
+        })
+    }
+
+    // Issues a nonce that needs no storage until it is spent: random bytes, its expiry and a
+    // MAC over both, all hex so it stays alphanumeric as EIP-4361 requires.
+    pub fn issue_nonce(&self, expires_at: i64) -> String {
+        let mut payload = rand::random::<[u8; 16]>().to_vec();
+        payload.extend_from_slice(&expires_at.to_be_bytes());
+        let mac = self.nonce_mac(&payload).finalize().into_bytes();
+        payload.extend_from_slice(&mac[..NONCE_MAC_LEN]);
+        hex::encode(payload)
+    }
+
+    // When a nonce from issue_nonce expires, or None if this service did not issue it
+    pub fn nonce_expiry(&self, nonce: &str) -> Option<i64> {
+        let bytes = hex::decode(nonce).ok()?;
+        if bytes.len() != 16 + 8 + NONCE_MAC_LEN {
+            return None;
+        }
+        let (payload, mac) = bytes.split_at(16 + 8);
+        self.nonce_mac(payload).verify_truncated_left(mac).ok()?;
+        Some(i64::from_be_bytes(payload[16..].try_into().ok()?))
+    }
+
+    fn nonce_mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.nonce_key).expect("HMAC accepts keys of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+pub fn generate_token() -> String {
+    format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        hex::encode(rand::random::<[u8; 32]>())
+    )
+}
+
+// The fields of an EIP-4361 message this service checks. The ones it has no use for (URI,
+// statement, request id, resources) are accepted but not kept.
+#[derive(Debug)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: EvmAddress,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+}
+
+impl SiweMessage {
+    pub fn parse(message: &str) -> Result<SiweMessage, String> {
+        let mut lines = message.lines();
+
+        let header = lines.next().unwrap_or_default();
+        let domain = header
+            .strip_suffix(PREAMBLE)
+            .ok_or_else(|| format!("Message must start with \"<domain>{}\"", PREAMBLE))?;
+        // The spec allows a scheme in front of the domain
+        let domain = domain.split_once("://").map_or(domain, |(_, rest)| rest);
+        let address = EvmAddress::parse("address", lines.next().unwrap_or_default())?;
+
+        let mut has_uri = false;
+        let mut version = None;
+        let mut chain_id = None;
+        let mut nonce = None;
+        let mut issued_at = None;
+        let mut expiration_time = None;
+        let mut not_before = None;
+        for line in lines {
+            let Some((key, value)) = line.split_once(": ") else {
+                // Blank lines, the statement, "Resources:" and its "- <uri>" entries
+                continue;
+            };
+            match key {
+                "URI" => has_uri = true,
+                "Version" => version = Some(value.to_string()),
+                "Chain ID" => {
+                    chain_id = Some(
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| format!("Invalid Chain ID: {}", value))?,
+                    )
+                }
+                "Nonce" => nonce = Some(value.to_string()),
+                "Issued At" => issued_at = Some(parse_time("Issued At", value)?),
+                "Expiration Time" => expiration_time = Some(parse_time("Expiration Time", value)?),
+                "Not Before" => not_before = Some(parse_time("Not Before", value)?),
+                _ => {}
+            }
+        }
+
+        if !has_uri {
+            return Err("Message has no URI".to_string());
+        }
+        Ok(SiweMessage {
+            domain: domain.to_string(),
+            address,
+            version: version.ok_or("Message has no Version")?,
+            chain_id: chain_id.ok_or("Message has no Chain ID")?,
+            nonce: nonce.ok_or("Message has no Nonce")?,
+            issued_at: issued_at.ok_or("Message has no Issued At")?,
+            expiration_time,
+            not_before,
+        })
+    }
+
+    // Everything that makes a parsed message good to sign in with, short of its nonce being
+    // unused: it is for this domain and chain, valid at `now`, and `raw` (the exact text it was
+    // parsed from) was signed by its address. Only externally owned accounts can sign in, since
+    // the signature must recover to the address.
+    pub fn verify(
+        &self,
+        raw: &str,
+        signature: &Signature,
+        domain: &str,
+        chain_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<(), ApiError> {
+        if self.version != "1" {
+            return Err(ApiError::bad_request(format!(
+                "Unsupported message version: {}",
+                self.version
+            )));
+        }
+        if self.domain != domain {
+            return Err(ApiError::unauthorized(format!(
+                "Message is for {}, not {}",
+                self.domain, domain
+            )));
+        }
+        if self.chain_id != chain_id {
+            return Err(ApiError::unauthorized(format!(
+                "Message is for chain {}, not {}",
+                self.chain_id, chain_id
+            )));
+        }
+        if self.expiration_time.is_some_and(|expiry| expiry <= now) {
+            return Err(ApiError::unauthorized("Message has expired"));
+        }
+        if self.not_before.is_some_and(|not_before| not_before > now) {
+            return Err(ApiError::unauthorized("Message is not valid yet"));
+        }
+        if self.issued_at > now + chrono::Duration::seconds(NONCE_TTL_SECS) {
+            return Err(ApiError::unauthorized("Message is issued in the future"));
+        }
+
+        let signer = signature
+            .recover(raw)
+            .map_err(|_| ApiError::unauthorized("Signature does not match the message"))?;
+        if signer != self.address.0 {
+            return Err(ApiError::unauthorized(format!(
+                "Message was signed by 0x{:x}, not {}",
+                signer, self.address
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn parse_time(field: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid {}: {}", field, e))
+}
+
+// The session a request signed in with, put in the request extensions by authenticate
+#[derive(Debug, Clone)]
+pub struct SessionIdentity {
+    pub id: i32,
+    pub address: EvmAddress,
+    pub expires_at: i64,
+}
+
+// Resolves a bearer session token to its session, if it is live
+pub fn find_session(conn: &mut PgConnection, token: &str) -> QueryResult<Option<SessionIdentity>> {
+    Ok(siwe_sessions::table
+        .filter(siwe_sessions::token_hash.eq(hash_key(token)))
+        .filter(siwe_sessions::revoked_at.is_null())
+        .filter(siwe_sessions::expires_at.gt(Utc::now().timestamp()))
+        .select((
+            siwe_sessions::id,
+            siwe_sessions::address,
+            siwe_sessions::expires_at,
+        ))
+        .first::<(i32, EvmAddress, i64)>(conn)
+        .optional()?
+        .map(|(id, address, expires_at)| SessionIdentity {
+            id,
+            address,
+            expires_at,
+        }))
+}
+
+// The signed-in wallet of a request. Handlers that only need it when present take
+// Option<SignedIn>.
+pub struct SignedIn(pub SessionIdentity);
+
+impl FromRequestParts<Arc<AppState>> for SignedIn {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<SessionIdentity>()
+            .cloned()
+            .map(SignedIn)
+            .ok_or_else(|| {
+                ApiError::unauthorized(
+                    "Sign in with Ethereum first and send the session token as Authorization: Bearer <token>",
+                )
+            })
+    }
+}
+
+impl OptionalFromRequestParts<Arc<AppState>> for SignedIn {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &Arc<AppState>,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<SessionIdentity>()
+            .cloned()
+            .map(SignedIn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::utils::hash_message;
+
+    const DOMAIN: &str = "switch.example";
+    const CHAIN_ID: u64 = 1;
+    const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    fn wallet(key: &str) -> LocalWallet {
+        key.parse().unwrap()
+    }
+
+    fn message(domain: &str, address: EvmAddress, extra: &str) -> String {
+        format!(
+            "{}{}\n{}\n\nSign in to Switch\n\nURI: https://{}\nVersion: 1\nChain ID: 1\nNonce: abc123\nIssued At: 2025-09-01T12:00:00Z{}",
+            domain, PREAMBLE, address, DOMAIN, extra
+        )
+    }
+
+    fn sign(wallet: &LocalWallet, message: &str) -> Signature {
+        wallet.sign_hash(hash_message(message)).unwrap()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        parse_time("now", time).unwrap()
+    }
+
+    fn siwe_config(secret: &str) -> SiweConfig {
+        SiweConfig {
+            domain: Some(DOMAIN.to_string()),
+            session_ttl_secs: 86400,
+            nonce_key: secret.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn issued_nonces_carry_their_expiry() {
+        let config = siwe_config("secret");
+        let nonce = config.issue_nonce(1_756_728_600);
+        assert!(nonce.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(config.nonce_expiry(&nonce), Some(1_756_728_600));
+        assert_ne!(config.issue_nonce(1_756_728_600), nonce);
+    }
+
+    #[test]
+    fn forged_and_foreign_nonces_are_refused() {
+        let config = siwe_config("secret");
+        let nonce = config.issue_nonce(1_756_728_600);
+        assert_eq!(siwe_config("other").nonce_expiry(&nonce), None);
+
+        // Pushing the expiry out invalidates the MAC
+        let mut bytes = hex::decode(&nonce).unwrap();
+        bytes[16] ^= 1;
+        assert_eq!(config.nonce_expiry(&hex::encode(&bytes)), None);
+
+        assert_eq!(config.nonce_expiry(&nonce[..nonce.len() - 2]), None);
+        assert_eq!(config.nonce_expiry("abc123"), None);
+        assert_eq!(config.nonce_expiry("not hex"), None);
+    }
+
+    #[test]
+    fn parse_reads_the_fields() {
+        let address = EvmAddress(wallet(KEY).address());
+        let parsed = SiweMessage::parse(&message(
+            DOMAIN,
+            address,
+            "\nExpiration Time: 2025-09-02T12:00:00Z\nNot Before: 2025-09-01T11:00:00Z\nResources:\n- https://switch.example/terms",
+        ))
+        .unwrap();
+        assert_eq!(parsed.domain, DOMAIN);
+        assert_eq!(parsed.address, address);
+        assert_eq!(parsed.version, "1");
+        assert_eq!(parsed.chain_id, CHAIN_ID);
+        assert_eq!(parsed.nonce, "abc123");
+        assert_eq!(parsed.issued_at, at("2025-09-01T12:00:00Z"));
+        assert_eq!(parsed.expiration_time, Some(at("2025-09-02T12:00:00Z")));
+        assert_eq!(parsed.not_before, Some(at("2025-09-01T11:00:00Z")));
+    }
+
+    #[test]
+    fn parse_strips_a_scheme_from_the_domain() {
+        let address = EvmAddress(wallet(KEY).address());
+        let parsed =
+            SiweMessage::parse(&message(&format!("https://{}", DOMAIN), address, "")).unwrap();
+        assert_eq!(parsed.domain, DOMAIN);
+    }
+
+    #[test]
+    fn parse_rejects_missing_fields() {
+        let full = message(DOMAIN, EvmAddress(wallet(KEY).address()), "");
+        for (line, error) in [
+            ("URI: ", "Message has no URI"),
+            ("Version: ", "Message has no Version"),
+            ("Chain ID: ", "Message has no Chain ID"),
+            ("Nonce: ", "Message has no Nonce"),
+            ("Issued At: ", "Message has no Issued At"),
+        ] {
+            let without = full
+                .lines()
+                .filter(|l| !l.starts_with(line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert_eq!(SiweMessage::parse(&without).unwrap_err(), error);
+        }
+        assert!(SiweMessage::parse("switch.example\n0x00").is_err());
+    }
+
+    #[test]
+    fn verify_accepts_a_message_signed_by_its_address() {
+        let wallet = wallet(KEY);
+        let raw = message(DOMAIN, EvmAddress(wallet.address()), "");
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        parsed
+            .verify(
+                &raw,
+                &sign(&wallet, &raw),
+                DOMAIN,
+                CHAIN_ID,
+                at("2025-09-01T12:05:00Z"),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_wrong_domain_and_chain() {
+        let wallet = wallet(KEY);
+        let raw = message(DOMAIN, EvmAddress(wallet.address()), "");
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        let signature = sign(&wallet, &raw);
+        let now = at("2025-09-01T12:05:00Z");
+        assert!(matches!(
+            parsed.verify(&raw, &signature, "evil.example", CHAIN_ID, now),
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            parsed.verify(&raw, &signature, DOMAIN, 5, now),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn verify_enforces_expiry_and_not_before() {
+        let wallet = wallet(KEY);
+        let raw = message(
+            DOMAIN,
+            EvmAddress(wallet.address()),
+            "\nExpiration Time: 2025-09-01T13:00:00Z\nNot Before: 2025-09-01T12:30:00Z",
+        );
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        let signature = sign(&wallet, &raw);
+        for (now, error) in [
+            ("2025-09-01T12:00:00Z", "Message is not valid yet"),
+            ("2025-09-01T13:00:00Z", "Message has expired"),
+        ] {
+            match parsed.verify(&raw, &signature, DOMAIN, CHAIN_ID, at(now)) {
+                Err(ApiError::Unauthorized(message)) => assert_eq!(message, error),
+                other => panic!("expected {:?} at {}, got {:?}", error, now, other),
+            }
+        }
+        parsed
+            .verify(
+                &raw,
+                &signature,
+                DOMAIN,
+                CHAIN_ID,
+                at("2025-09-01T12:45:00Z"),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_a_message_issued_in_the_future() {
+        let wallet = wallet(KEY);
+        let raw = message(DOMAIN, EvmAddress(wallet.address()), "");
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        assert!(matches!(
+            parsed.verify(
+                &raw,
+                &sign(&wallet, &raw),
+                DOMAIN,
+                CHAIN_ID,
+                at("2025-09-01T11:00:00Z"),
+            ),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn verify_rejects_a_signature_from_another_wallet() {
+        let owner = wallet(KEY);
+        let other = wallet("0123456789012345678901234567890123456789012345678901234567890123");
+        let raw = message(DOMAIN, EvmAddress(owner.address()), "");
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        match parsed.verify(
+            &raw,
+            &sign(&other, &raw),
+            DOMAIN,
+            CHAIN_ID,
+            at("2025-09-01T12:05:00Z"),
+        ) {
+            Err(ApiError::Unauthorized(message)) => {
+                assert!(message.starts_with("Message was signed by"), "{}", message)
+            }
+            other => panic!("expected a wrong signer error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_rejects_a_signature_over_different_text() {
+        let wallet = wallet(KEY);
+        let raw = message(DOMAIN, EvmAddress(wallet.address()), "");
+        let parsed = SiweMessage::parse(&raw).unwrap();
+        let signature = sign(&wallet, &format!("{}\n", raw));
+        assert!(matches!(
+            parsed.verify(
+                &raw,
+                &signature,
+                DOMAIN,
+                CHAIN_ID,
+                at("2025-09-01T12:05:00Z")
+            ),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::address::EvmAddress;
+use crate::auth::ApiKeyIdentity;
+use crate::error::ApiError;
+use crate::schema::{api_client_quotas, gas_spend};
+use crate::wei::Wei;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Numeric, Text};
+use ethers::prelude::*;
+use eyre::Result;
+use std::env;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+
+// Caps on the gas a signing wallet may spend, read from the environment as decimal wei:
+// - SPEND_MAX_TX_WEI: worst-case cost (gas limit * gas price) of a single transaction
+// - SPEND_MAX_HOUR_WEI / SPEND_MAX_DAY_WEI: rolling totals per signing wallet
+// - SPEND_CLIENT_MAX_DAY_WEI: rolling daily total per API client (the API key a request
+//   authenticated with), unless the client has its own row in api_client_quotas
+// Unset limits are not enforced.
+#[derive(Debug, Clone, Default)]
+pub struct SpendingPolicy {
+    max_tx_wei: Option<U256>,
+    max_hour_wei: Option<U256>,
+    max_day_wei: Option<U256>,
+    client_max_day_wei: Option<U256>,
+}
+
+impl SpendingPolicy {
+    pub fn from_env() -> Result<SpendingPolicy> {
+        let policy = SpendingPolicy {
+            max_tx_wei: limit_from_env("SPEND_MAX_TX_WEI")?,
+            max_hour_wei: limit_from_env("SPEND_MAX_HOUR_WEI")?,
+            max_day_wei: limit_from_env("SPEND_MAX_DAY_WEI")?,
+            client_max_day_wei: limit_from_env("SPEND_CLIENT_MAX_DAY_WEI")?,
+        };
+        println!("Spending policy: {:?}", policy);
+        Ok(policy)
+    }
+
+    // Books the worst-case cost of a transaction before it is sent, refusing it if that would
+    // take the wallet or the client over budget. Everything already reserved counts, settled or
+    // not. The check and the booking run in one transaction holding a lock per wallet and per
+    // client, so concurrent requests are checked one after another against what the others
+    // reserved, and can't all pass against the same remaining budget.
+    pub fn reserve(
+        &self,
+        conn: &mut PgConnection,
+        wallet: H160,
+        client: &str,
+        cost: U256,
+    ) -> Result<Reservation, ApiError> {
+        conn.transaction(|conn| {
+            // Always wallet first, then client, so two requests can't wait on each other
+            let wallet = EvmAddress(wallet).to_string();
+            for key in [
+                format!("gas_spend:{}", wallet),
+                format!("gas_spend:{}", client),
+            ] {
+                diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                    .bind::<Text, _>(key)
+                    .execute(conn)?;
+            }
+            self.check(conn, &wallet, client, cost)?;
+
+            let id = diesel::insert_into(gas_spend::table)
+                .values((
+                    gas_spend::wallet.eq(&wallet),
+                    gas_spend::client.eq(client),
+                    gas_spend::cost_wei.eq(Wei(cost)),
+                    gas_spend::spent_at.eq(chrono::Utc::now().timestamp()),
+                ))
+                .returning(gas_spend::id)
+                .get_result::<i32>(conn)?;
+            Ok(Reservation(id))
+        })
+    }
+
+    fn check(
+        &self,
+        conn: &mut PgConnection,
+        wallet: &str,
+        client: &str,
+        cost: U256,
+    ) -> Result<(), ApiError> {
+        if let Some(max_tx_wei) = self.max_tx_wei
+            && cost > max_tx_wei
+        {
+            return Err(ApiError::TooManyRequests(format!(
+                "Spending limit: transaction may cost {} wei, over the per-transaction limit of {} wei",
+                cost, max_tx_wei
+            )));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        for (window, label, limit) in [
+            (HOUR, "hourly", self.max_hour_wei),
+            (DAY, "daily", self.max_day_wei),
+        ] {
+            let Some(limit) = limit else { continue };
+            let spent = spent_since(
+                conn,
+                gas_spend::table
+                    .filter(gas_spend::wallet.eq(wallet))
+                    .filter(gas_spend::spent_at.ge(now - window))
+                    .into_boxed(),
+            )?;
+            if spent.saturating_add(cost) > limit {
+                return Err(ApiError::TooManyRequests(format!(
+                    "Spending limit: wallet {} has spent {} wei of its {} limit of {} wei, this transaction may cost {} wei",
+                    wallet, spent, label, limit, cost
+                )));
+            }
+        }
+
+        let client_quota = api_client_quotas::table
+            .find(client)
+            .select(api_client_quotas::max_day_wei)
+            .first::<Wei>(conn)
+            .optional()?
+            .map(|max_day_wei| max_day_wei.0)
+            .or(self.client_max_day_wei);
+        if let Some(limit) = client_quota {
+            let spent = spent_since(
+                conn,
+                gas_spend::table
+                    .filter(gas_spend::client.eq(client))
+                    .filter(gas_spend::spent_at.ge(now - DAY))
+                    .into_boxed(),
+            )?;
+            if spent.saturating_add(cost) > limit {
+                return Err(ApiError::TooManyRequests(format!(
+                    "Spending limit: client {} has spent {} wei of its daily quota of {} wei, this transaction may cost {} wei",
+                    client, spent, limit, cost
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Budget booked by SpendingPolicy::reserve for a transaction about to be sent. Once the send
+// returns it must be either tied to the transaction or cancelled; one left behind by a crash
+// in between just counts against the budget until it ages out of the windows.
+#[must_use]
+pub struct Reservation(i32);
+
+impl Reservation {
+    // Ties the reservation to the transaction it was made for, so settle and release find it
+    pub fn broadcast(self, conn: &mut PgConnection, txn_hash: H256) -> Result<(), ApiError> {
+        diesel::update(gas_spend::table.find(self.0))
+            .set(gas_spend::txn_hash.eq(format!("0x{}", hex::encode(txn_hash))))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    // A transaction that was never sent never paid anything
+    pub fn cancel(self, conn: &mut PgConnection) -> Result<(), ApiError> {
+        diesel::delete(gas_spend::table.find(self.0)).execute(conn)?;
+        Ok(())
+    }
+}
+
+// Replaces the reservation with what the receipt says was actually paid
+pub fn settle(conn: &mut PgConnection, receipt: &TransactionReceipt) -> Result<(), ApiError> {
+    let (Some(gas_used), Some(gas_price)) = (receipt.gas_used, receipt.effective_gas_price) else {
+        return Ok(());
+    };
+    diesel::update(
+        gas_spend::table
+            .filter(gas_spend::txn_hash.eq(format!("0x{}", hex::encode(receipt.transaction_hash)))),
+    )
+    .set((
+        gas_spend::cost_wei.eq(Wei(gas_used * gas_price)),
+        gas_spend::settled.eq(true),
+    ))
+    .execute(conn)?;
+    Ok(())
+}
+
+// A dropped transaction never paid anything
+pub fn release(conn: &mut PgConnection, txn_hash: H256) -> Result<(), ApiError> {
+    diesel::delete(
+        gas_spend::table.filter(gas_spend::txn_hash.eq(format!("0x{}", hex::encode(txn_hash)))),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+fn spent_since(
+    conn: &mut PgConnection,
+    query: gas_spend::BoxedQuery<'_, diesel::pg::Pg>,
+) -> Result<U256, ApiError> {
+    let spent = query
+        .select(sql::<Numeric>("COALESCE(SUM(cost_wei), 0)"))
+        .first::<Wei>(conn)?;
+    Ok(spent.0)
+}
+
+fn limit_from_env(name: &str) -> Result<Option<U256>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(
+            U256::from_dec_str(value.trim()).map_err(|e| eyre::eyre!("Invalid {}: {}", name, e))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+// The API client a request is billed to: the API key it authenticated with, named
+// "api_key:<id>" in gas_spend and api_client_quotas. Taken from authenticate, not from
+// anything the caller sends, so a client can't start a fresh budget by renaming itself.
+pub struct ApiClient(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiClient {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let api_key =
+            <ApiKeyIdentity as FromRequestParts<S>>::from_request_parts(parts, state).await?;
+        Ok(ApiClient(format!("api_key:{}", api_key.id)))
+    }
+}
@@ -1,40 +1,118 @@
 use crate::contract_calls::{
-    get_all_contract_assets::__path_get_all_contract_assets, get_asset::__path_get_asset,
-    get_my_assets::__path_get_my_assets, register_asset::__path_register_asset,
+    build_transaction::{__path_build_register_transaction, __path_build_transfer_transaction},
+    get_all_contract_assets::__path_get_all_contract_assets,
+    get_asset::__path_get_asset,
+    get_my_assets::__path_get_my_assets,
+    get_owner_assets::__path_get_owner_assets,
+    register_asset::__path_register_asset,
+    register_batch::__path_register_batch,
+    relay::__path_relay_transaction,
     transfer_asset::__path_transfer_asset,
+    transfer_batch::__path_transfer_batch,
 };
 
+use crate::address::EvmAddress;
+use crate::asset_id::AssetId;
+use crate::auth::{API_KEY_HEADER, Role};
+use crate::error::ErrorResponse;
+use crate::event_log::EventKind;
 use crate::handlers::{
     analytics::__path_get_analytics,
-    assets::__path_get_all_assets,
-    search::__path_search_events,
+    api_keys::{
+        __path_create_api_key, __path_list_api_key_writes, __path_list_api_keys,
+        __path_revoke_api_key,
+    },
+    asset_audit::{__path_audit_assets, __path_derive_id, __path_verify_asset_id},
+    assets::{__path_get_all_assets, __path_get_asset_detail},
+    custody::{__path_create_custody_user, __path_get_custody_user},
+    owners::{__path_get_my_profile, __path_get_owner_profile},
+    search::{__path_search_asset_events, __path_search_events},
+    siwe::{
+        __path_get_siwe_nonce, __path_get_siwe_session, __path_logout_siwe, __path_verify_siwe,
+    },
+    stream::__path_stream_events,
+    transactions::__path_get_transaction,
     transfer::{
         __path_get_assets_by_owner, __path_get_transfers_by_asset, __path_get_transfers_by_date,
     },
+    wallet::{__path_get_metrics, __path_get_wallet_balance},
+    webhooks::{
+        __path_create_webhook, __path_delete_webhook, __path_get_webhook,
+        __path_list_webhook_deliveries, __path_list_webhooks, __path_redeliver_webhook,
+        __path_update_webhook,
+    },
 };
 use crate::models::{
-    ApiResponse, Asset, GetAssetInput, OwnershipTransferredResponse, RegisterAssetInput,
-    SearchInput, Transfer, TransferAssetInput, TransferByDate,
+    ApiKeyResponse, ApiKeyWrite, ApiResponse, Asset, AssetAuditResponse, AssetDetailResponse,
+    AssetEvent, AssetRegistration, AssetSearchHit, AssetVerification, BatchItemResult,
+    BatchJobResponse, BuildRegisterInput, BuildTransferInput, CreateApiKeyInput,
+    CreateCustodyUserInput, CustodyUserResponse, DeriveAssetIdInput, DerivedAssetIdResponse,
+    EventSearchInput, GetAssetInput, OwnerHoldingsResponse, OwnerLabel, OwnerProfileResponse,
+    OwnershipTransferredResponse, ProvenanceHop, RegisterAssetInput, RegisterBatchInput,
+    RelayInput, RelayResponse, SearchInput, SiweNonceResponse, SiweSessionResponse,
+    SiweVerifyInput, TransactionRecord, Transfer, TransferAssetInput, TransferBatchInput,
+    TransferByDate, TransferWithTransaction, UnsignedTransactionResponse, WalletBalanceResponse,
+    WebhookDelivery, WebhookInput, WebhookPayload, WebhookResponse,
 };
-use utoipa::OpenApi;
+use crate::pagination::{AssetSort, EventSort, SortDirection, TransferSort};
+use crate::wei::Wei;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         get_all_assets,
+        get_asset_detail,
         get_transfers_by_asset,
         get_assets_by_owner,
         get_asset,
         transfer_asset,
+        transfer_batch,
         register_asset,
+        register_batch,
         get_my_assets,
+        get_owner_assets,
+        get_owner_profile,
+        get_my_profile,
         get_all_contract_assets,
         search_events,
+        search_asset_events,
+        stream_events,
+        create_webhook,
+        list_webhooks,
+        get_webhook,
+        update_webhook,
+        delete_webhook,
+        list_webhook_deliveries,
+        redeliver_webhook,
+        create_api_key,
+        list_api_keys,
+        revoke_api_key,
+        list_api_key_writes,
+        get_siwe_nonce,
+        verify_siwe,
+        get_siwe_session,
+        logout_siwe,
         get_transfers_by_date,
-        get_analytics
+        get_analytics,
+        create_custody_user,
+        get_custody_user,
+        build_register_transaction,
+        build_transfer_transaction,
+        relay_transaction,
+        get_wallet_balance,
+        get_metrics,
+        get_transaction,
+        derive_id,
+        verify_asset_id,
+        audit_assets
     ),
     components(
         schemas(
+            AssetId,
+            EvmAddress,
+            Wei,
             Asset,
             Transfer,
             ApiResponse<Vec<Asset>>,
@@ -45,11 +123,106 @@ use utoipa::OpenApi;
             ApiResponse<Asset>,
             OwnershipTransferredResponse,
             SearchInput,
-            TransferByDate
+            TransferByDate,
+            RegisterBatchInput,
+            BatchItemResult,
+            BatchJobResponse,
+            TransferBatchInput,
+            CreateCustodyUserInput,
+            CustodyUserResponse,
+            ApiResponse<CustodyUserResponse>,
+            BuildRegisterInput,
+            BuildTransferInput,
+            UnsignedTransactionResponse,
+            ApiResponse<UnsignedTransactionResponse>,
+            RelayInput,
+            RelayResponse,
+            WalletBalanceResponse,
+            ApiResponse<WalletBalanceResponse>,
+            TransactionRecord,
+            TransferWithTransaction,
+            ApiResponse<TransactionRecord>,
+            ApiResponse<Vec<TransferWithTransaction>>,
+            OwnerHoldingsResponse,
+            ApiResponse<OwnerHoldingsResponse>,
+            DeriveAssetIdInput,
+            DerivedAssetIdResponse,
+            AssetVerification,
+            AssetAuditResponse,
+            ApiResponse<DerivedAssetIdResponse>,
+            ApiResponse<AssetVerification>,
+            ApiResponse<AssetAuditResponse>,
+            SortDirection,
+            AssetSort,
+            TransferSort,
+            EventKind,
+            EventSort,
+            EventSearchInput,
+            AssetEvent,
+            ApiResponse<Vec<AssetEvent>>,
+            AssetSearchHit,
+            ApiResponse<Vec<AssetSearchHit>>,
+            AssetRegistration,
+            ProvenanceHop,
+            AssetDetailResponse,
+            ApiResponse<AssetDetailResponse>,
+            OwnerLabel,
+            OwnerProfileResponse,
+            ApiResponse<OwnerProfileResponse>,
+            WebhookInput,
+            WebhookResponse,
+            ApiResponse<WebhookResponse>,
+            ApiResponse<Vec<WebhookResponse>>,
+            WebhookDelivery,
+            ApiResponse<WebhookDelivery>,
+            ApiResponse<Vec<WebhookDelivery>>,
+            WebhookPayload,
+            Role,
+            CreateApiKeyInput,
+            ApiKeyResponse,
+            ApiResponse<ApiKeyResponse>,
+            ApiResponse<Vec<ApiKeyResponse>>,
+            ApiKeyWrite,
+            ApiResponse<Vec<ApiKeyWrite>>,
+            SiweNonceResponse,
+            ApiResponse<SiweNonceResponse>,
+            SiweVerifyInput,
+            SiweSessionResponse,
+            ApiResponse<SiweSessionResponse>,
+            ErrorResponse
         )
     ),
+    modifiers(&ApiKeyAuth),
+    security(
+        (),
+        ("api_key" = []),
+        ("siwe_session" = [])
+    ),
     tags(
         (name = "SwitchAssets", description = "API for managing blockchain assets")
     )
 )]
 pub struct ApiDoc;
+
+// Lets Swagger UI send an x-api-key header, which write and admin endpoints require, and a
+// Sign-In with Ethereum session token, which "my" endpoints resolve to the signed-in wallet
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        components.add_security_scheme(
+            "siwe_session",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Session token from POST /auth/verify"))
+                    .build(),
+            ),
+        );
+    }
+}
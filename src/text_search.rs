@@ -0,0 +1,131 @@
+use crate::asset_id::AssetId;
+use crate::error::ApiError;
+use crate::models::{Asset, AssetSearchHit};
+use crate::schema::assets;
+use diesel::dsl::{AsExprOf, sql};
+use diesel::expression::{SqlLiteral, UncheckedBind};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Float, Text};
+use std::collections::HashMap;
+
+// assets.description_tsv is not in schema.rs, since diesel has no tsvector type; it is only
+// ever read through these fragments. It is generated with the 'english' configuration, and
+// queries must use the same one to match.
+pub type TsExpression<ST> = SqlLiteral<ST, UncheckedBind<SqlLiteral<ST>, AsExprOf<String, Text>>>;
+
+// The description with HTML special characters escaped, so the <mark> tags ts_headline adds
+// are the only markup in a snippet. Postgres' parser reads the entities as single tokens, so
+// words still match and fragments never split an entity.
+const ESCAPED_DESCRIPTION: &str = "replace(replace(replace(replace(replace(\
+    assets.description, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;')";
+
+const HEADLINE_OPTIONS: &str =
+    "StartSel=<mark>, StopSel=</mark>, MinWords=10, MaxWords=30, MaxFragments=2";
+
+// Turns a user query into to_tsquery syntax: every word must match, and a word ending in `*`
+// matches as a prefix (`reg*` finds "registered" and "registry"). Operators and punctuation
+// are dropped so that no input can make to_tsquery fail.
+pub fn parse_query(q: &str) -> Result<String, ApiError> {
+    let terms = q
+        .split_whitespace()
+        .filter_map(|word| {
+            let prefix = word.ends_with('*');
+            let lexeme = word
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase();
+            match (lexeme.is_empty(), prefix) {
+                (true, _) => None,
+                (false, true) => Some(format!("{}:*", lexeme)),
+                (false, false) => Some(lexeme),
+            }
+        })
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        return Err(ApiError::bad_request("q has no searchable words"));
+    }
+    Ok(terms.join(" & "))
+}
+
+// Whether the description matches a query from parse_query
+pub fn matches(tsquery: &str) -> TsExpression<Bool> {
+    sql::<Bool>("assets.description_tsv @@ to_tsquery('english', ")
+        .bind::<Text, _>(tsquery.to_string())
+        .sql(")")
+}
+
+// How well the description matches, higher is better
+pub fn rank(tsquery: &str) -> TsExpression<Float> {
+    sql::<Float>("ts_rank(assets.description_tsv, to_tsquery('english', ")
+        .bind::<Text, _>(tsquery.to_string())
+        .sql("))")
+}
+
+// The parts of the description around the matches, HTML-escaped, with matched words wrapped
+// in <mark>
+pub fn headline(tsquery: &str) -> TsExpression<Text> {
+    sql::<Text>(&format!(
+        "ts_headline('english', {}, to_tsquery('english', ",
+        ESCAPED_DESCRIPTION
+    ))
+    .bind::<Text, _>(tsquery.to_string())
+    .sql(&format!("), '{}')", HEADLINE_OPTIONS))
+}
+
+// Adds rank and snippet to a page of assets that was ordered by something else
+pub fn with_snippets(
+    conn: &mut PgConnection,
+    tsquery: &str,
+    page: Vec<Asset>,
+) -> Result<Vec<AssetSearchHit>, ApiError> {
+    let ids = page.iter().map(|asset| asset.asset_id).collect::<Vec<_>>();
+    let mut scored = assets::table
+        .filter(assets::asset_id.eq_any(ids))
+        .select((assets::asset_id, rank(tsquery), headline(tsquery)))
+        .load::<(AssetId, f32, String)>(conn)?
+        .into_iter()
+        .map(|(asset_id, rank, snippet)| (asset_id, (rank, snippet)))
+        .collect::<HashMap<_, _>>();
+    Ok(page
+        .into_iter()
+        .map(|asset| {
+            let (rank, snippet) = scored.remove(&asset.asset_id).unzip();
+            AssetSearchHit {
+                asset,
+                rank,
+                snippet,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query_ands_lowercased_words() {
+        assert_eq!(parse_query("Gold  Bar").unwrap(), "gold & bar");
+    }
+
+    #[test]
+    fn parse_query_keeps_trailing_star_as_prefix_match() {
+        assert_eq!(parse_query("reg* vault").unwrap(), "reg:* & vault");
+        assert_eq!(parse_query("*reg").unwrap(), "reg");
+    }
+
+    #[test]
+    fn parse_query_drops_tsquery_operators() {
+        assert_eq!(
+            parse_query("gold & !(silver | 'bar'):").unwrap(),
+            "gold & silver & bar"
+        );
+    }
+
+    #[test]
+    fn parse_query_rejects_input_without_words() {
+        assert!(parse_query("").is_err());
+        assert!(parse_query("  & | ! *").is_err());
+    }
+}
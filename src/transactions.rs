@@ -0,0 +1,151 @@
+use crate::address::EvmAddress;
+use crate::schema::transactions;
+use crate::wei::Wei;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use ethers::prelude::*;
+use eyre::Result;
+
+pub const TX_STATUS_SUCCESS: &str = "success";
+pub const TX_STATUS_REVERTED: &str = "reverted";
+
+// Records a receipt for a transaction this API sent. Bookkeeping only: the write itself has
+// already happened, so failures are logged rather than failing the request. Takes the pool
+// rather than a connection so that none is held while the node is asked for the details.
+pub async fn record_sent<M: Middleware>(
+    client: &M,
+    db_pool: &Pool<ConnectionManager<PgConnection>>,
+    receipt: &TransactionReceipt,
+) {
+    let recorded = async {
+        let details = fetch_details(client, receipt).await?;
+        let conn = &mut db_pool.get()?;
+        store_receipt(conn, receipt, &details, true)
+    };
+    if let Err(e) = recorded.await {
+        eprintln!(
+            "Failed to record transaction {:?}: {:?}",
+            receipt.transaction_hash, e
+        );
+    }
+}
+
+// Records the transaction behind an indexed event, fetching its receipt unless it is
+// already stored
+pub async fn record_indexed<M: Middleware>(
+    client: &M,
+    conn: &mut PgConnection,
+    txn_hash: H256,
+) -> Result<()> {
+    let exists = transactions::table
+        .find(format!("0x{}", hex::encode(txn_hash)))
+        .select(transactions::txn_hash)
+        .first::<String>(conn)
+        .optional()?
+        .is_some();
+    if exists {
+        return Ok(());
+    }
+
+    let receipt = client
+        .get_transaction_receipt(txn_hash)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to fetch receipt for {:?}: {}", txn_hash, e))?
+        .ok_or_else(|| eyre::eyre!("No receipt for indexed transaction {:?}", txn_hash))?;
+    let details = fetch_details(client, &receipt).await?;
+    store_receipt(conn, &receipt, &details, false)
+}
+
+// What a receipt does not say about its transaction
+struct ChainDetails {
+    nonce: Option<i64>,
+    block_timestamp: Option<i64>,
+}
+
+async fn fetch_details<M: Middleware>(
+    client: &M,
+    receipt: &TransactionReceipt,
+) -> Result<ChainDetails> {
+    let nonce = client
+        .get_transaction(receipt.transaction_hash)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to fetch transaction: {}", e))?
+        .map(|tx| to_i64("nonce", tx.nonce))
+        .transpose()?;
+    let block_timestamp = match receipt.block_number {
+        Some(block_number) => client
+            .get_block(block_number)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch block {}: {}", block_number, e))?
+            .map(|block| to_i64("block timestamp", block.timestamp))
+            .transpose()?,
+        None => None,
+    };
+    Ok(ChainDetails {
+        nonce,
+        block_timestamp,
+    })
+}
+
+fn store_receipt(
+    conn: &mut PgConnection,
+    receipt: &TransactionReceipt,
+    details: &ChainDetails,
+    sent_by_api: bool,
+) -> Result<()> {
+    // OP-stack receipts carry the L1 data fee as an extra field
+    let l1_fee = receipt
+        .other
+        .get_deserialized::<U256>("l1Fee")
+        .and_then(|l1_fee| l1_fee.ok());
+    let l2_fee = receipt
+        .gas_used
+        .zip(receipt.effective_gas_price)
+        .map(|(gas_used, gas_price)| gas_used * gas_price);
+    let total_fee = l2_fee.map(|l2_fee| l2_fee + l1_fee.unwrap_or_default());
+    let gas_used = receipt
+        .gas_used
+        .map(|gas_used| to_i64("gas used", gas_used))
+        .transpose()?;
+
+    let status = if receipt.status == Some(1.into()) {
+        TX_STATUS_SUCCESS
+    } else {
+        TX_STATUS_REVERTED
+    };
+
+    let insert = diesel::insert_into(transactions::table).values((
+        transactions::txn_hash.eq(format!("0x{}", hex::encode(receipt.transaction_hash))),
+        transactions::from_address.eq(EvmAddress(receipt.from).to_string()),
+        transactions::to_address.eq(receipt.to.map(|to| EvmAddress(to).to_string())),
+        transactions::nonce.eq(details.nonce),
+        transactions::block_number.eq(receipt.block_number.map(|block| block.as_u64() as i64)),
+        transactions::block_timestamp.eq(details.block_timestamp),
+        transactions::status.eq(status),
+        transactions::gas_used.eq(gas_used),
+        transactions::effective_gas_price_wei.eq(receipt.effective_gas_price.map(Wei)),
+        transactions::l1_fee_wei.eq(l1_fee.map(Wei)),
+        transactions::total_fee_wei.eq(total_fee.map(Wei)),
+        transactions::sent_by_api.eq(sent_by_api),
+        transactions::recorded_at.eq(chrono::Utc::now().timestamp()),
+    ));
+    // The indexer may have seen an API-sent transaction first; it is still ours
+    if sent_by_api {
+        insert
+            .on_conflict(transactions::txn_hash)
+            .do_update()
+            .set(transactions::sent_by_api.eq(true))
+            .execute(conn)?;
+    } else {
+        insert
+            .on_conflict(transactions::txn_hash)
+            .do_nothing()
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+// Counts that fit in BIGINT on any real chain; one that doesn't is refused rather than clamped
+fn to_i64(field: &str, value: U256) -> Result<i64> {
+    i64::try_from(value).map_err(|_| eyre::eyre!("{} {} is out of range", field, value))
+}
@@ -0,0 +1,334 @@
+use crate::address::EvmAddress;
+use crate::app_state::AppState;
+use crate::asset_id::AssetId;
+use crate::event_log::EventKind;
+use crate::models::{AssetEvent, WebhookDelivery, WebhookPayload, WebhookSubscription};
+use crate::schema::{asset_events, webhook_deliveries, webhook_subscriptions};
+use diesel::prelude::*;
+use eyre::Result;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed";
+
+const SIGNATURE_HEADER: &str = "x-switch-signature";
+const TIMESTAMP_HEADER: &str = "x-switch-timestamp";
+const EVENT_HEADER: &str = "x-switch-event";
+const DELIVERY_HEADER: &str = "x-switch-delivery";
+
+// A failed delivery is retried after 10s, 20s, 40s, ... up to an hour apart
+const BASE_BACKOFF_SECS: i64 = 10;
+const MAX_BACKOFF_SECS: i64 = 3600;
+// Due deliveries sent per poll
+const DELIVERY_BATCH: i64 = 50;
+
+// Works off the webhook outbox. Configured from the environment:
+// - WEBHOOK_POLL_SECS: seconds between checks for due deliveries, default 5
+// - WEBHOOK_MAX_ATTEMPTS: attempts before a delivery is marked failed, default 8
+// - WEBHOOK_TIMEOUT_SECS: how long an endpoint has to answer, default 10
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    poll_interval: Duration,
+    max_attempts: i32,
+    http: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn from_env() -> Result<WebhookDispatcher> {
+        let poll_secs = match env::var("WEBHOOK_POLL_SECS") {
+            Ok(value) => value
+                .parse::<u64>()
+                .map_err(|e| eyre::eyre!("Invalid WEBHOOK_POLL_SECS: {}", e))?,
+            Err(_) => 5,
+        };
+        let max_attempts = match env::var("WEBHOOK_MAX_ATTEMPTS") {
+            Ok(value) => value
+                .parse::<i32>()
+                .map_err(|e| eyre::eyre!("Invalid WEBHOOK_MAX_ATTEMPTS: {}", e))?,
+            Err(_) => 8,
+        };
+        let timeout_secs = match env::var("WEBHOOK_TIMEOUT_SECS") {
+            Ok(value) => value
+                .parse::<u64>()
+                .map_err(|e| eyre::eyre!("Invalid WEBHOOK_TIMEOUT_SECS: {}", e))?,
+            Err(_) => 10,
+        };
+
+        Ok(WebhookDispatcher {
+            poll_interval: Duration::from_secs(poll_secs.max(1)),
+            max_attempts: max_attempts.max(1),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(timeout_secs.max(1)))
+                .build()?,
+        })
+    }
+}
+
+// A random signing secret for a subscription that did not bring its own
+pub fn generate_secret() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+// The x-switch-signature of a delivery: HMAC-SHA256 over "<timestamp>.<body>" keyed with the
+// subscription secret. Receivers recompute it from the x-switch-timestamp header and the raw
+// body, and can reject old timestamps to stop replays.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// Queues a newly recorded event for every active subscription it matches. Runs in the
+// transaction that records the event, so an event is never stored without its deliveries.
+pub fn enqueue_deliveries(
+    conn: &mut PgConnection,
+    event_id: i32,
+    kind: EventKind,
+    asset_id: AssetId,
+    from_address: Option<EvmAddress>,
+    to_address: EvmAddress,
+) -> QueryResult<()> {
+    let addresses = from_address
+        .into_iter()
+        .chain([to_address])
+        .collect::<Vec<_>>();
+    let subscription_ids = webhook_subscriptions::table
+        .filter(webhook_subscriptions::active.eq(true))
+        .filter(
+            webhook_subscriptions::event_kind
+                .is_null()
+                .or(webhook_subscriptions::event_kind.eq(kind)),
+        )
+        .filter(
+            webhook_subscriptions::asset_id
+                .is_null()
+                .or(webhook_subscriptions::asset_id.eq(asset_id)),
+        )
+        .filter(
+            webhook_subscriptions::address
+                .is_null()
+                .or(webhook_subscriptions::address.eq_any(addresses)),
+        )
+        .select(webhook_subscriptions::id)
+        .load::<i32>(conn)?;
+    if subscription_ids.is_empty() {
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let rows = subscription_ids
+        .into_iter()
+        .map(|subscription_id| {
+            (
+                webhook_deliveries::subscription_id.eq(subscription_id),
+                webhook_deliveries::event_id.eq(event_id),
+                webhook_deliveries::status.eq(STATUS_PENDING),
+                webhook_deliveries::next_attempt_at.eq(now),
+                webhook_deliveries::created_at.eq(now),
+            )
+        })
+        .collect::<Vec<_>>();
+    diesel::insert_into(webhook_deliveries::table)
+        .values(&rows)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+// What an endpoint made of one delivery attempt. error is None when it answered 2xx.
+struct Attempt {
+    response_status: Option<i32>,
+    error: Option<String>,
+}
+
+pub async fn deliver_webhooks(state: Arc<AppState>) -> Result<()> {
+    let mut interval = tokio::time::interval(state.webhooks.poll_interval);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = deliver_due(&state).await {
+            eprintln!("Webhook delivery error: {:?}", e);
+        }
+    }
+}
+
+// Sends every pending delivery whose retry time has come, concurrently, and records how each
+// went. Deliveries of a deactivated subscription wait until it is active again. No pooled
+// connection is held while endpoints answer, which can take the whole timeout.
+async fn deliver_due(state: &AppState) -> Result<()> {
+    let dispatcher = &state.webhooks;
+    let due = load_due(state)?;
+
+    let attempts = join_all(
+        due.into_iter()
+            .map(|(delivery, subscription, event)| async move {
+                let attempt = dispatcher.send(&delivery, &subscription, event).await;
+                (delivery, attempt)
+            }),
+    )
+    .await;
+
+    let conn = &mut state.db_pool.get()?;
+    for (delivery, attempt) in attempts {
+        record_attempt(conn, &delivery, attempt, dispatcher.max_attempts)?;
+    }
+    Ok(())
+}
+
+fn load_due(state: &AppState) -> Result<Vec<(WebhookDelivery, WebhookSubscription, AssetEvent)>> {
+    let conn = &mut state.db_pool.get()?;
+    let due = webhook_deliveries::table
+        .inner_join(webhook_subscriptions::table)
+        .inner_join(asset_events::table)
+        .filter(webhook_deliveries::status.eq(STATUS_PENDING))
+        .filter(webhook_deliveries::next_attempt_at.le(chrono::Utc::now().timestamp()))
+        .filter(webhook_subscriptions::active.eq(true))
+        .order((
+            webhook_deliveries::next_attempt_at.asc(),
+            webhook_deliveries::id.asc(),
+        ))
+        .limit(DELIVERY_BATCH)
+        .select((
+            WebhookDelivery::as_select(),
+            WebhookSubscription::as_select(),
+            AssetEvent::as_select(),
+        ))
+        .load::<(WebhookDelivery, WebhookSubscription, AssetEvent)>(conn)?;
+    Ok(due)
+}
+
+impl WebhookDispatcher {
+    async fn send(
+        &self,
+        delivery: &WebhookDelivery,
+        subscription: &WebhookSubscription,
+        event: AssetEvent,
+    ) -> Attempt {
+        let kind = event.kind;
+        let payload = WebhookPayload {
+            delivery_id: delivery.id,
+            subscription_id: subscription.id,
+            event,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                return Attempt {
+                    response_status: None,
+                    error: Some(format!("Failed to encode payload: {}", e)),
+                };
+            }
+        };
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let response = self
+            .http
+            .post(&subscription.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, kind.as_str())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                sign(&subscription.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => Attempt {
+                response_status: Some(response.status().as_u16() as i32),
+                error: None,
+            },
+            Ok(response) => Attempt {
+                response_status: Some(response.status().as_u16() as i32),
+                error: Some(format!("Endpoint answered {}", response.status())),
+            },
+            Err(e) => Attempt {
+                response_status: None,
+                error: Some(format!("Request failed: {}", e)),
+            },
+        }
+    }
+}
+
+fn record_attempt(
+    conn: &mut PgConnection,
+    delivery: &WebhookDelivery,
+    attempt: Attempt,
+    max_attempts: i32,
+) -> QueryResult<()> {
+    let now = chrono::Utc::now().timestamp();
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at, delivered_at) = match attempt.error {
+        None => (STATUS_DELIVERED, delivery.next_attempt_at, Some(now)),
+        Some(_) if attempts >= max_attempts => (STATUS_FAILED, delivery.next_attempt_at, None),
+        Some(_) => (STATUS_PENDING, now + backoff_secs(attempts), None),
+    };
+
+    // A delivery requeued while this attempt was in flight keeps its fresh retry budget
+    diesel::update(
+        webhook_deliveries::table
+            .find(delivery.id)
+            .filter(webhook_deliveries::attempts.eq(delivery.attempts)),
+    )
+    .set((
+        webhook_deliveries::status.eq(status),
+        webhook_deliveries::attempts.eq(attempts),
+        webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+        webhook_deliveries::last_attempt_at.eq(now),
+        webhook_deliveries::response_status.eq(attempt.response_status),
+        webhook_deliveries::last_error.eq(attempt.error),
+        webhook_deliveries::delivered_at.eq(delivered_at),
+    ))
+    .execute(conn)?;
+    Ok(())
+}
+
+// Seconds to wait after the given number of failed attempts
+fn backoff_secs(attempts: i32) -> i64 {
+    let doublings = (attempts - 1).clamp(0, 20) as u32;
+    (BASE_BACKOFF_SECS << doublings).min(MAX_BACKOFF_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_is_hmac_sha256_over_timestamp_and_body() {
+        assert_eq!(
+            sign("whsec", 1700000000, br#"{"a":1}"#),
+            "sha256=8ad37ba156048ae0e0a5533c75cdf26fee88b07f93cb57ee4c80adb053012032"
+        );
+    }
+
+    #[test]
+    fn sign_depends_on_secret_timestamp_and_body() {
+        let signature = sign("whsec", 1700000000, b"{}");
+        assert_ne!(sign("other", 1700000000, b"{}"), signature);
+        assert_ne!(sign("whsec", 1700000001, b"{}"), signature);
+        assert_ne!(sign("whsec", 1700000000, b"[]"), signature);
+    }
+
+    #[test]
+    fn backoff_doubles_from_the_base_up_to_the_cap() {
+        assert_eq!(backoff_secs(0), BASE_BACKOFF_SECS);
+        assert_eq!(backoff_secs(1), BASE_BACKOFF_SECS);
+        assert_eq!(backoff_secs(2), BASE_BACKOFF_SECS * 2);
+        assert_eq!(backoff_secs(3), BASE_BACKOFF_SECS * 4);
+        assert_eq!(backoff_secs(9), BASE_BACKOFF_SECS * 256);
+        assert_eq!(backoff_secs(10), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(i32::MAX), MAX_BACKOFF_SECS);
+    }
+}
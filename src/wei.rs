@@ -0,0 +1,217 @@
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::data_types::PgNumeric;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Numeric;
+use ethers::types::U256;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use utoipa::openapi::RefOr;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+use utoipa::{PartialSchema, ToSchema};
+
+// Postgres stores NUMERIC as base-10000 digits
+const NBASE: u64 = 10_000;
+
+// An amount of wei, stored as NUMERIC(78, 0) so any U256 fits, and returned as a decimal
+// string since amounts routinely exceed what a JSON number holds exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Numeric)]
+pub struct Wei(pub U256);
+
+impl From<U256> for Wei {
+    fn from(wei: U256) -> Self {
+        Wei(wei)
+    }
+}
+
+impl fmt::Display for Wei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Wei {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl From<Wei> for PgNumeric {
+    fn from(wei: Wei) -> Self {
+        let mut digits = Vec::new();
+        let mut value = wei.0;
+        while !value.is_zero() {
+            let (rest, digit) = value.div_mod(U256::from(NBASE));
+            digits.push(digit.as_u64() as i16);
+            value = rest;
+        }
+        // Most significant first, with trailing zero digits implied by the weight, as Postgres
+        // writes them
+        let weight = digits.len() as i16 - 1;
+        digits.reverse();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        PgNumeric::Positive {
+            weight: weight.max(0),
+            scale: 0,
+            digits,
+        }
+    }
+}
+
+impl TryFrom<PgNumeric> for Wei {
+    type Error = String;
+
+    fn try_from(numeric: PgNumeric) -> Result<Self, Self::Error> {
+        let (weight, digits) = match numeric {
+            PgNumeric::Positive { weight, digits, .. } => (i32::from(weight), digits),
+            PgNumeric::Negative { .. } => return Err("Negative wei amount".to_string()),
+            PgNumeric::NaN => return Err("Wei amount is NaN".to_string()),
+        };
+        let overflow = || "Wei amount does not fit in 256 bits".to_string();
+        let mut value = U256::zero();
+        // digits[i] is worth 10000^(weight - i)
+        for (i, &digit) in digits.iter().enumerate() {
+            if !(0..NBASE as i16).contains(&digit) {
+                return Err(format!("Invalid NUMERIC digit {}", digit));
+            }
+            if weight - (i as i32) < 0 {
+                if digit != 0 {
+                    return Err("Wei amount has a fractional part".to_string());
+                }
+                continue;
+            }
+            value = value
+                .checked_mul(U256::from(NBASE))
+                .and_then(|value| value.checked_add(U256::from(digit as u64)))
+                .ok_or_else(overflow)?;
+        }
+        for _ in (digits.len() as i32)..=weight {
+            value = value.checked_mul(U256::from(NBASE)).ok_or_else(overflow)?;
+        }
+        Ok(Wei(value))
+    }
+}
+
+impl ToSql<Numeric, Pg> for Wei {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let numeric = PgNumeric::from(*self);
+        ToSql::<Numeric, Pg>::to_sql(&numeric, &mut out.reborrow())
+    }
+}
+
+impl FromSql<Numeric, Pg> for Wei {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        Ok(Wei::try_from(PgNumeric::from_sql(bytes)?)?)
+    }
+}
+
+impl PartialSchema for Wei {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some("^[0-9]+$"))
+            .description(Some("Amount in wei as a decimal string"))
+            .examples(["21000000000000"])
+            .into()
+    }
+}
+
+impl ToSchema for Wei {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(wei: U256) -> U256 {
+        Wei::try_from(PgNumeric::from(Wei(wei))).unwrap().0
+    }
+
+    #[test]
+    fn numeric_round_trips_the_full_u256_range() {
+        for wei in [
+            U256::zero(),
+            U256::from(1),
+            U256::from(9_999),
+            U256::from(10_000),
+            U256::from(10_001),
+            U256::from(100_000_000),
+            U256::from(i64::MAX as u64) + 1,
+            U256::from_dec_str("123000000000000000000").unwrap(),
+            U256::MAX,
+        ] {
+            assert_eq!(round_trip(wei), wei);
+        }
+    }
+
+    #[test]
+    fn numeric_is_written_like_postgres_does() {
+        assert_eq!(
+            PgNumeric::from(Wei(U256::from(200_000_000_u64))),
+            PgNumeric::Positive {
+                weight: 2,
+                scale: 0,
+                digits: vec![2],
+            }
+        );
+        assert_eq!(
+            PgNumeric::from(Wei(U256::from(10_002_u64))),
+            PgNumeric::Positive {
+                weight: 1,
+                scale: 0,
+                digits: vec![1, 2],
+            }
+        );
+        assert_eq!(
+            PgNumeric::from(Wei(U256::zero())),
+            PgNumeric::Positive {
+                weight: 0,
+                scale: 0,
+                digits: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn numeric_that_is_not_a_u256_is_rejected() {
+        // 1.5
+        assert!(
+            Wei::try_from(PgNumeric::Positive {
+                weight: 0,
+                scale: 1,
+                digits: vec![1, 5000],
+            })
+            .is_err()
+        );
+        // 1.0 is still a whole amount
+        assert_eq!(
+            Wei::try_from(PgNumeric::Positive {
+                weight: 0,
+                scale: 1,
+                digits: vec![1, 0],
+            }),
+            Ok(Wei(U256::from(1)))
+        );
+        assert!(
+            Wei::try_from(PgNumeric::Negative {
+                weight: 0,
+                scale: 0,
+                digits: vec![1],
+            })
+            .is_err()
+        );
+        assert!(Wei::try_from(PgNumeric::NaN).is_err());
+        // 10000^20 is past U256::MAX (about 1.16 * 10^77)
+        assert!(
+            Wei::try_from(PgNumeric::Positive {
+                weight: 20,
+                scale: 0,
+                digits: vec![1],
+            })
+            .is_err()
+        );
+    }
+}